@@ -2,32 +2,21 @@
 
 extern crate proc_macro;
 
-use proof::tree_arithmetic::{log_base_two, next_power_of_two};
 use proc_macro::TokenStream;
-use proc_macro2;
 use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
-#[derive(Clone)]
-struct LeafData<'a> {
-    ident: &'a syn::Ident,
-    ty: &'a syn::Type,
-    offset: u8,
-    size: u8,
-    is_primitive: bool,
-}
-
 /// Returns a Vec of `syn::Ident` for each named field in the struct, whilst filtering out fields
 /// that should not be accounted for in the merkle proof.
 ///
 /// # Panics
 /// Any unnamed struct field (like in a tuple struct) will raise a panic at compile time.
-fn get_named_field_idents<'a>(struct_data: &'a syn::DataStruct) -> Vec<&'a syn::Ident> {
+fn get_named_field_idents(struct_data: &syn::DataStruct) -> Vec<&syn::Ident> {
     struct_data
         .fields
         .iter()
         .filter_map(|f| {
-            if should_skip(&f) {
+            if should_skip(f) {
                 None
             } else {
                 Some(match &f.ident {
@@ -41,11 +30,11 @@ fn get_named_field_idents<'a>(struct_data: &'a syn::DataStruct) -> Vec<&'a syn::
 
 /// Returns a Vec of `syn::Type` for each named field in the struct, whilst filtering out fields
 /// that should not be accounted for in the merkle proof.
-fn get_field_types<'a>(struct_data: &'a syn::DataStruct) -> Vec<&'a syn::Type> {
+fn get_field_types(struct_data: &syn::DataStruct) -> Vec<&syn::Type> {
     struct_data
         .fields
         .iter()
-        .filter_map(|f| if should_skip(&f) { None } else { Some(&f.ty) })
+        .filter_map(|f| if should_skip(f) { None } else { Some(&f.ty) })
         .collect()
 }
 
@@ -63,190 +52,68 @@ fn should_skip(field: &syn::Field) -> bool {
     false
 }
 
-/// Returns a Vec of data required to generate the nodes for each leaf index.
-fn get_leaf_data_from_fields<'a>(
-    idents: Vec<&'a syn::Ident>,
-    types: Vec<&'a syn::Type>,
-) -> Vec<Vec<LeafData<'a>>> {
-    let mut offset = 0;
-    let mut ret: Vec<Vec<LeafData<'a>>> = vec![];
-    let mut leaf: Vec<LeafData<'a>> = vec![];
-
-    for it in idents.iter().zip(types.iter()) {
-        let (ident, ty) = it;
-        let (size, is_primitive) = get_type_info(ty);
-
-        if offset + size > 32 {
-            ret.push(leaf.clone());
-            leaf.drain(..);
-            offset = 0;
-        }
-
-        leaf.push(LeafData {
-            ident,
-            ty,
-            offset,
-            size,
-            is_primitive,
-        });
-
-        offset += size;
-    }
-
-    ret.push(leaf.clone());
-
-    ret
-}
-
-/// Returns the size of the type (in bytes) and a boolean to denote whether the type is a primitive
-/// of SSZ or it is a composite.
-fn get_type_info(ty: &syn::Type) -> (u8, bool) {
-    match ty {
-        syn::Type::Path(syn::TypePath { path, .. }) => {
-            return match path.segments[0].ident.to_string().as_ref() {
-                "bool" => (1, true),
-                "u8" => (1, true),
-                "u16" => (2, true),
-                "u32" => (4, true),
-                "u64" => (8, true),
-                "u128" => (16, true),
-                "U256" => (32, true),
-                _ => (32, false),
-            };
-        }
-        _ => (),
-    }
-
-    (32, false)
-}
-
-fn generate_node(index: u64, leaf_data: &Vec<LeafData>) -> proc_macro2::TokenStream {
-    if leaf_data[0].is_primitive {
-        let primitive_nodes: Vec<proc_macro2::TokenStream> = leaf_data
-            .iter()
-            .map(|p| {
-                let LeafData {
-                    ident,
-                    offset,
-                    size,
-                    ..
-                } = p;
-                let ident = ident.to_string();
-
-                quote! {
-                    proof::field::Primitive {
-                        index: #index,
-                        ident: #ident.to_owned(),
-                        size: #size,
-                        offset: #offset,
-                    }
-                }
-            })
-            .collect();
-
-        quote! {
-            proof::field::Node::Primitive(vec![
-                #(#primitive_nodes),*
-            ])
-        }
-    } else {
-        let LeafData { ident, ty, .. } = leaf_data[0];
-        let ident = ident.to_string();
-        quote! {
-            proof::field::Node::Composite(
-                proof::field::Composite {
-                    index: #index,
-                    ident: #ident.to_owned(),
-                    height: <#ty>::height(),
-                }
-            )
-        }
-    }
-}
-
-/// Returns a vector of `TokenStreams` consisting of if branches which match all field idents
-/// specified in `leaf_data` and return the coresponding `Node`.
-fn build_if_chain<'a>(
-    leaf_data: &Vec<Vec<LeafData<'a>>>,
-    height: u64,
-) -> Vec<proc_macro2::TokenStream> {
-    leaf_data
-        .iter()
-        .clone()
-        .enumerate()
-        .map(|(i, leaf)| {
-            leaf.iter().map(|field| {
-                // leaf_index = first leaf + current chunk
-                let leaf_index = (1_u64 << height) - 1 + i as u64;
-
-                let ident = field.ident.to_string();
-                let ty = field.ty;
-
-                // Builds the `Node` that should be returned upon a match of the path.
-                let ret_node = generate_node(leaf_index, leaf);
-
-                // Build the coresponding matcher for each field ident and its coresponding chunk.
-                // If the path terminates, retrieve the specified node. Otherwise, recusively
-                // request the node from the field's type for `path[1..]`. This matcher will never
-                // need to match a `Path::Index(_)` type.
-                quote! {
-                    if Some(&proof::Path::Ident(#ident.to_string())) == path.first() {
-                        if path.len() == 1 {
-                            return Ok(#ret_node);
-                        } else {
-                            let node = <#ty>::get_node(path[1..].to_vec())?;
-                            let index = proof::tree_arithmetic::zeroed::subtree_index_to_general(#leaf_index, node.get_index());
-
-                            return Ok(proof::impls::replace_index(node.clone(), index));
-                        }
-                    }
-                }
-            }).collect()
-        })
-        .collect()
-}
-
-/// Implements `merkle_partial::merkle_tree_overlay::MerkleTreeOverlay` for some `struct`.
+/// Implements `proof::MerkleTreeOverlay` and `proof::MerkleTreeValue` for some `struct`.
+///
+/// Fields are stored in the merkle tree in the order they appear in the struct, each as the root
+/// of the subtree of its type. The overlay is generated by
+/// `proof::impl_merkle_overlay_for_container!` and every field type must implement both traits.
 ///
-/// Fields are stored in the merkle tree in the order they appear in the struct.
+/// # Panics
+/// Generic structs and structs without any fields will raise a panic at compile time.
 #[proc_macro_derive(Provable)]
 pub fn proof_derive(input: TokenStream) -> TokenStream {
     let item = parse_macro_input!(input as DeriveInput);
 
     let name = &item.ident;
-    let (impl_generics, ty_generics, where_clause) = &item.generics.split_for_impl();
+
+    if !item.generics.params.is_empty() {
+        panic!("proof_derive does not support generic structs.");
+    }
 
     let struct_data = match &item.data {
         syn::Data::Struct(s) => s,
         _ => panic!("proof_derive only supports structs."),
     };
 
-    // Parse the struct into a vector of data elements which contain the necessary information to
-    // implement the rest of the trait.
-    let leaf_data = get_leaf_data_from_fields(
-        get_named_field_idents(&struct_data),
-        get_field_types(&struct_data),
-    );
+    let idents = get_named_field_idents(struct_data);
+    let types = get_field_types(struct_data);
 
-    // Calculate the height of the tree needed to represent all the elements in the struct.
-    let height = log_base_two(next_power_of_two(leaf_data.len() as u64));
+    if idents.is_empty() {
+        panic!("proof_derive requires at least one field.");
+    }
 
-    // Build the if chain for `get_node`
-    let if_chain = build_if_chain(&leaf_data, height);
+    // The position of each field's chunk among the leaves of the container's tree.
+    let positions: Vec<u64> = (0..idents.len() as u64).collect();
+    let leaves = idents.len() as u64;
+    let (idents, values) = (&idents, &idents);
 
     let output = quote! {
-        impl #impl_generics proof::MerkleTreeOverlay for #name #ty_generics #where_clause {
-            fn height() -> u8 {
-                #height as u8
-            }
-
-            fn get_node(path: Vec<proof::Path>) -> Result<proof::field::Node, proof::Error> {
-                #(#if_chain else)*
-                if let Some(p) = path.first() {
-                    Err(proof::Error::InvalidPath(p.clone()))
-                } else {
-                    Err(proof::Error::EmptyPath())
-                }
+        proof::impl_merkle_overlay_for_container!(#name, [#(#idents: #types),*]);
+
+        impl proof::MerkleTreeValue for #name {
+            fn merkleize<H: proof::Hasher, S: proof::NodeStore>(
+                &self,
+                db: &mut proof::backend::Backend<H, S>,
+                root: proof::NodeIndex,
+            ) -> Result<(), proof::Error> {
+                use proof::tree_arithmetic::zeroed::{
+                    checked_left_most_leaf, checked_subtree_index_to_general,
+                };
+
+                let height = <Self as proof::MerkleTreeOverlay>::height();
+                let first = checked_left_most_leaf(0, height).ok_or(proof::Error::IndexOverflow())?;
+
+                #(
+                    proof::MerkleTreeValue::merkleize(
+                        &self.#values,
+                        db,
+                        checked_subtree_index_to_general(root, first + #positions as proof::NodeIndex)
+                            .ok_or(proof::Error::IndexOverflow())?,
+                    )?;
+                )*
+
+                db.hash_subtree(root, height, #leaves)?;
+                Ok(())
             }
         }
     };
@@ -1,13 +1,16 @@
-#![allow(unused)]
-
 use ethereum_types::U256;
-use proof::cache::hash_children;
-use proof::field::{Composite, Node, Primitive};
-use proof::{Error, MerkleTreeOverlay, Proof, Path, SerializedProof};
+use proof::node::Node;
+use proof::types::{FixedVector, VariableList};
+use proof::{
+    hash_children, Chunk, Error, MerkleTreeOverlay, Path, PathElement, Proof, SerializedProof,
+};
 use proof_derive::Provable;
-use ssz_types::{FixedVector, VariableList};
 use typenum::{U32, U8};
 
+fn path(s: &str) -> Vec<PathElement> {
+    s.parse::<Path>().unwrap().into()
+}
+
 #[derive(Debug, Default, Provable)]
 pub struct A {
     a: U256,
@@ -18,43 +21,43 @@ pub struct A {
 
 #[test]
 fn basic_overlay() {
+    assert_eq!(A::height(), 2);
+    assert_eq!(A::max_depth(), 2);
+
     assert_eq!(
-        A::get_node(vec![Path::Ident("a".to_string())]),
-        Ok(Node::Primitive(vec![Primitive {
+        A::get_node(path("a")),
+        Ok(Node {
             index: 3,
-            ident: "a".to_string(),
+            ident: PathElement::from_ident_str("a"),
             size: 32,
             offset: 0,
-        }]))
+            height: 0,
+            is_list: false,
+        })
     );
 
+    assert_eq!(A::get_node(path("b")).map(|node| node.index), Ok(4));
+
+    // Every field is the root of its own chunk
     assert_eq!(
-        A::get_node(vec![Path::Ident("b".to_string())]),
-        Ok(Node::Primitive(vec![Primitive {
-            index: 4,
-            ident: "b".to_string(),
-            size: 32,
+        A::get_node(path("c")),
+        Ok(Node {
+            index: 5,
+            ident: PathElement::from_ident_str("c"),
+            size: 16,
             offset: 0,
-        }]))
+            height: 0,
+            is_list: false,
+        })
     );
+    assert_eq!(A::get_node(path("d")).map(|node| node.index), Ok(6));
 
-    assert_eq!(
-        A::get_node(vec![Path::Ident("c".to_string())]),
-        Ok(Node::Primitive(vec![
-            Primitive {
-                index: 5,
-                ident: "c".to_string(),
-                size: 16,
-                offset: 0,
-            },
-            Primitive {
-                index: 5,
-                ident: "d".to_string(),
-                size: 16,
-                offset: 16,
-            }
-        ]))
-    );
+    for s in &["a", "b", "c", "d"] {
+        assert_eq!(
+            A::get_path(A::get_node(path(s)).unwrap().index),
+            Some(path(s))
+        );
+    }
 }
 
 #[test]
@@ -67,39 +70,33 @@ fn basic_proof() {
     one.to_little_endian(&mut arr[0..32]);
     two.to_little_endian(&mut arr[32..64]);
     arr[64] = 3;
-    arr[80] = 4;
+    arr[96] = 4;
 
     let proof = SerializedProof {
         indices: vec![3, 4, 5, 6],
         chunks: arr.to_vec(),
     };
 
-    let mut p = Proof::<A>::new(proof.clone());
-
-    assert_eq!(
-        p.get_bytes(vec![Path::Ident("a".to_string())]),
-        Ok(arr[0..32].to_vec())
-    );
-
-    assert_eq!(
-        p.get_bytes(vec![Path::Ident("b".to_string())]),
-        Ok(arr[32..64].to_vec())
-    );
+    let mut p = Proof::<A>::new(proof);
+    assert_eq!(p.fill(), Ok(()));
 
-    assert_eq!(
-        p.get_bytes(vec![Path::Ident("c".to_string())]),
-        Ok(arr[64..80].to_vec())
-    );
+    assert_eq!(p.get_bytes(path("a")), Ok(arr[0..32].to_vec()));
+    assert_eq!(p.get_bytes(path("b")), Ok(arr[32..64].to_vec()));
+    assert_eq!(p.get_bytes(path("c")), Ok(arr[64..80].to_vec()));
+    assert_eq!(p.get_bytes(path("d")), Ok(arr[96..112].to_vec()));
 
     assert_eq!(
-        p.get_bytes(vec![Path::Ident("d".to_string())]),
-        Ok(arr[80..96].to_vec())
+        p.get_bytes(path("e")),
+        Err(Error::InvalidPath(PathElement::from_ident_str("e")))
     );
 
-    assert_eq!(
-        p.get_bytes(vec![Path::Ident("e".to_string())]),
-        Err(Error::InvalidPath(Path::Ident("e".to_string())))
-    );
+    let value = A {
+        a: one,
+        b: two,
+        c: 3,
+        d: 4,
+    };
+    assert_eq!(Proof::<A>::from_value(&value).root(), p.root());
 }
 
 #[derive(Provable)]
@@ -111,35 +108,28 @@ struct B {
 #[test]
 fn simple_fixed_vector() {
     assert_eq!(B::height(), 1);
+    assert_eq!(B::max_depth(), 3);
 
     assert_eq!(
-        B::get_node(vec![Path::Ident("a".to_string())]),
-        Ok(Node::Primitive(vec![Primitive {
-            ident: "a".to_string(),
+        B::get_node(path("a")),
+        Ok(Node {
             index: 1,
+            ident: PathElement::from_ident_str("a"),
             size: 8,
             offset: 0,
-        }]))
+            height: 0,
+            is_list: false,
+        })
     );
 
     for i in 0..4 {
-        assert_eq!(
-            B::get_node(vec![Path::Ident("b".to_string()), Path::Index(2 * i)]),
-            Ok(Node::Primitive(vec![
-                Primitive {
-                    ident: (2 * i).to_string(),
-                    index: 11 + i,
-                    size: 16,
-                    offset: 0,
-                },
-                Primitive {
-                    ident: (2 * i + 1).to_string(),
-                    index: 11 + i,
-                    size: 16,
-                    offset: 16,
-                }
-            ]))
-        );
+        for j in 0..2 {
+            let node = B::get_node(path(&format!("b[{}]", 2 * i + j))).unwrap();
+            assert_eq!(
+                (node.index, node.size, node.offset),
+                (11 + i, 16, 16 * j as u8)
+            );
+        }
     }
 }
 
@@ -152,29 +142,14 @@ struct C {
 
 #[test]
 fn single_node() {
-    assert_eq!(
-        C::get_node(vec![Path::Ident("a".to_string())]),
-        Ok(Node::Primitive(vec![
-            Primitive {
-                ident: "a".to_string(),
-                index: 0,
-                size: 1,
-                offset: 0,
-            },
-            Primitive {
-                ident: "b".to_string(),
-                index: 0,
-                size: 2,
-                offset: 1,
-            },
-            Primitive {
-                ident: "c".to_string(),
-                index: 0,
-                size: 4,
-                offset: 3,
-            }
-        ]))
-    );
+    for (s, index, size) in &[("a", 3, 1), ("b", 4, 2), ("c", 5, 4)] {
+        let node = C::get_node(path(s)).unwrap();
+        assert_eq!((node.index, node.size, node.offset), (*index, *size, 0));
+    }
+
+    // The fourth leaf is padding
+    assert_eq!(C::get_path(6), None);
+    assert_eq!(C::zero_subtree_height(6), None);
 }
 
 #[derive(Debug, Default, Provable)]
@@ -188,11 +163,11 @@ struct State {
     messages: VariableList<Message, U8>,
 }
 
-fn zero_hash(depth: u8) -> Vec<u8> {
+fn zero_hash(depth: u8) -> Chunk {
     if depth == 0 {
-        vec![0; 32]
+        Chunk::default()
     } else if depth == 1 {
-        hash_children(&[0; 32], &[0; 32])
+        hash_children(&Chunk::default(), &Chunk::default())
     } else {
         let last = zero_hash(depth - 1);
         hash_children(&last, &last)
@@ -208,22 +183,22 @@ fn roundtrip_partial() {
     arr[0] = 1;
 
     // 32 `message[0].message`
-    arr[32..64].copy_from_slice(&vec![1_u8; 32]);
+    arr[32..64].copy_from_slice(&[1_u8; 32]);
 
     // 33 `message[1].timestamp`
     arr[64] = 2;
 
     // 34 `message[1].message`
-    arr[96..128].copy_from_slice(&vec![42_u8; 32]);
+    arr[96..128].copy_from_slice(&[42_u8; 32]);
 
-    // 8 `hash of message[2] and message[3]`
-    arr[128..160].copy_from_slice(&zero_hash(2));
+    // 8 `hash of the zeroed chunks padding message[2] and message[3]`
+    arr[128..160].copy_from_slice(zero_hash(1).as_ref());
 
-    // 4 `hash of message[4..7]`
-    arr[160..192].copy_from_slice(&zero_hash(3));
+    // 4 `hash of the zeroed chunks padding message[4..7]`
+    arr[160..192].copy_from_slice(zero_hash(2).as_ref());
 
     // 2 length mixin
-    arr[223] = 2;
+    arr[192] = 2;
 
     let sp = SerializedProof {
         indices: vec![31, 32, 33, 34, 8, 4, 2],
@@ -235,41 +210,29 @@ fn roundtrip_partial() {
 
     // TESTING TIMESTAMPS
     assert_eq!(
-        proof.get_bytes(vec![
-            Path::Ident("messages".to_string()),
-            Path::Index(0),
-            Path::Ident("timestamp".to_string())
-        ]),
+        proof.get_bytes(path("messages[0].timestamp")),
         Ok(vec![1, 0, 0, 0, 0, 0, 0, 0])
     );
 
     assert_eq!(
-        proof.get_bytes(vec![
-            Path::Ident("messages".to_string()),
-            Path::Index(1),
-            Path::Ident("timestamp".to_string())
-        ]),
+        proof.get_bytes(path("messages[1].timestamp")),
         Ok(vec![2, 0, 0, 0, 0, 0, 0, 0])
     );
 
     // TESTING MESSAGES
+    assert_eq!(proof.get_bytes(path("messages[0].message[1]")), Ok(vec![1]));
     assert_eq!(
-        proof.get_bytes(vec![
-            Path::Ident("messages".to_string()),
-            Path::Index(0),
-            Path::Ident("message".to_string()),
-            Path::Index(1),
-        ]),
-        Ok(vec![1])
-    );
-
-    assert_eq!(
-        proof.get_bytes(vec![
-            Path::Ident("messages".to_string()),
-            Path::Index(1),
-            Path::Ident("message".to_string()),
-            Path::Index(31),
-        ]),
+        proof.get_bytes(path("messages[1].message[31]")),
         Ok(vec![42])
     );
+
+    // The derived `MerkleTreeValue` builds the same tree
+    let message = |timestamp, byte| Message {
+        timestamp,
+        message: FixedVector::new(vec![byte; 32]).unwrap(),
+    };
+    let state = State {
+        messages: VariableList::new(vec![message(1, 1), message(2, 42)]).unwrap(),
+    };
+    assert_eq!(Proof::<State>::from_value(&state).root(), proof.root());
 }
@@ -0,0 +1,65 @@
+//! Builds a proof of a container declared with `impl_merkle_overlay_for_container!`, extracts
+//! the proof of one of its fields and verifies it against the root of the full value.
+//!
+//! `#[derive(Provable)]` generates the same two impls from the struct definition.
+
+use ethereum_types::U256;
+use proof::backend::Backend;
+use proof::tree_arithmetic::zeroed::checked_subtree_index_to_general;
+use proof::{
+    impl_merkle_overlay_for_container, Error, Hasher, MerkleTreeValue, NodeIndex, NodeStore, Path,
+    Proof,
+};
+use std::convert::TryFrom;
+
+// Checkpoint's merkle tree
+//
+//        root(0)
+//       /       \
+//  epoch(1)    root(2)
+struct Checkpoint {
+    epoch: u64,
+    root: U256,
+}
+
+impl_merkle_overlay_for_container!(Checkpoint, [epoch: u64, root: U256]);
+
+impl MerkleTreeValue for Checkpoint {
+    fn merkleize<H: Hasher, S: NodeStore>(
+        &self,
+        db: &mut Backend<H, S>,
+        root: NodeIndex,
+    ) -> Result<(), Error> {
+        let general =
+            |index| checked_subtree_index_to_general(root, index).ok_or(Error::IndexOverflow());
+
+        self.epoch.merkleize(db, general(1)?)?;
+        self.root.merkleize(db, general(2)?)?;
+        db.hash_subtree(root, 1, 2)?;
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let checkpoint = Checkpoint {
+        epoch: 42,
+        root: U256::from(7),
+    };
+
+    // Merkleize the full value
+    let full = Proof::<Checkpoint>::try_from_value(&checkpoint)?;
+    let root = full.root()?;
+
+    // Extract the proof of `root`, which is made up of the field and its sibling `epoch`
+    let path: Path = "root".parse()?;
+    let serialized = full.extract(path.clone())?;
+
+    let mut partial = Proof::<Checkpoint>::try_from(serialized)?;
+    partial.fill()?;
+    partial.verify(&root)?;
+
+    assert_eq!(partial.get::<U256>(path)?, checkpoint.root);
+    println!("verified the proof of Checkpoint.root against {:?}", root);
+
+    Ok(())
+}
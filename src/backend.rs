@@ -1,126 +1,544 @@
 use super::NodeIndex;
+use crate::chunk::Chunk;
 use crate::error::{Error, Result};
-use crate::tree_arithmetic::zeroed::expand_tree_index;
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-
-/// Stores the mapping of nodes to their chunks.
-#[derive(Clone, Debug, Default, PartialEq)]
-pub struct Backend {
-    db: HashMap<NodeIndex, Vec<u8>>,
+use crate::hasher::{Hasher, Sha256Hasher};
+use crate::node_log::NodeLog;
+use crate::store::NodeStore;
+use crate::tree_arithmetic::zeroed::{expand_tree_index, index_depth, sibling_index};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Stores the mapping of nodes to their chunks in `S`. The chunks are calculated using the hash
+/// function `H`.
+///
+/// A `Cache` opened with `open` also appends every write to a log on disk, see `commit`.
+#[derive(Debug)]
+pub struct Backend<H: Hasher = Sha256Hasher, S: NodeStore = HashMap<NodeIndex, Chunk>> {
+    db: S,
+    dirty: BTreeSet<NodeIndex>,
+    zero_subtree_height: fn(NodeIndex) -> Option<u64>,
+    log: Option<NodeLog>,
+    _phantom: PhantomData<H>,
 }
 
 impl Backend {
     /// Instantiate an empty `Cache`.
     pub fn new() -> Self {
-        Self { db: HashMap::new() }
+        Self::with_zero_subtrees(|_| None)
+    }
+}
+
+impl<H: Hasher, S: NodeStore> Backend<H, S> {
+    /// Instantiate an empty `Cache` which uses the hash function `H`.
+    pub fn with_hasher() -> Self
+    where
+        S: Default,
+    {
+        Self::with_zero_subtrees(|_| None)
     }
 
-    /// Gets a reference to the chunk coresponding to the node index.
-    pub fn get(&self, index: NodeIndex) -> Option<&Vec<u8>> {
-        self.db.get(&index)
+    /// Instantiate an empty `Cache` which treats a missing node as the root of an all-zero
+    /// subtree, if `zero_subtree_height` returns the height of that subtree.
+    ///
+    /// Only nodes without any loaded descendants are implied, and only when they are needed by
+    /// `fill`, `refresh` or `verify`.
+    pub fn with_zero_subtrees(zero_subtree_height: fn(NodeIndex) -> Option<u64>) -> Self
+    where
+        S: Default,
+    {
+        Self::with_store(S::default(), zero_subtree_height)
+    }
+
+    /// Instantiate a `Cache` which keeps its nodes in `store`, including any nodes that are
+    /// already stored. Missing nodes are implied as described by `with_zero_subtrees`.
+    pub fn with_store(store: S, zero_subtree_height: fn(NodeIndex) -> Option<u64>) -> Self {
+        Self {
+            db: store,
+            dirty: BTreeSet::new(),
+            zero_subtree_height,
+            log: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Opens the `Cache` persisted in the log at `path`, creating an empty log if the file does
+    /// not exist. Missing nodes are implied as described by `with_zero_subtrees`.
+    ///
+    /// The nodes are restored as of the last successful `commit`. Writes which were not committed,
+    /// as well as a truncated or corrupted tail of the log, are discarded.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        zero_subtree_height: fn(NodeIndex) -> Option<u64>,
+    ) -> io::Result<Self>
+    where
+        S: Default,
+    {
+        let (log, nodes) = NodeLog::open(path)?;
+        let mut ret = Self::with_zero_subtrees(zero_subtree_height);
+
+        for (index, chunk) in nodes {
+            ret.db.insert(index, chunk)?;
+        }
+
+        ret.log = Some(log);
+        Ok(ret)
+    }
+
+    /// Durably appends the writes made since the last commit to the log, marking the current
+    /// nodes as a consistent state to restore when the log is reopened. Does nothing if the
+    /// `Cache` was not opened with `open`.
+    pub fn commit(&mut self) -> io::Result<()> {
+        match self.log.as_mut() {
+            Some(log) => log.commit(self.db.indices()?.len(), self.db.get(0)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrites the log so that it only contains the current nodes, which are committed. Does
+    /// nothing if the `Cache` was not opened with `open`.
+    pub fn compact(&mut self) -> io::Result<()> {
+        match self.log.as_mut() {
+            Some(log) => log.compact(self.db.iter()),
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the chunk coresponding to the node index.
+    ///
+    /// Like every method which reads or writes the nodes, this returns `Error::Io` if the
+    /// `NodeStore` fails.
+    pub fn get(&self, index: NodeIndex) -> Result<Option<Chunk>> {
+        Ok(self.db.get(index)?)
     }
 
     /// Sets the chunk for the node index and returns the old value.
-    pub fn insert(&mut self, index: NodeIndex, chunk: Vec<u8>) -> Option<Vec<u8>> {
-        self.db.insert(index, chunk)
+    pub fn insert(&mut self, index: NodeIndex, chunk: Chunk) -> Result<Option<Chunk>> {
+        let old = self.db.insert(index, chunk)?;
+
+        if let Some(log) = self.log.as_mut() {
+            log.insert(index, chunk);
+        }
+
+        Ok(old)
+    }
+
+    /// Removes the chunk for the node index and returns its value.
+    pub fn remove(&mut self, index: NodeIndex) -> Result<Option<Chunk>> {
+        let old = self.db.remove(index)?;
+
+        if let Some(log) = self.log.as_mut() {
+            log.remove(index);
+        }
+
+        Ok(old)
+    }
+
+    /// Sets the chunk for the node index and marks it as dirty so that its ancestors are
+    /// recalculated on the next call to `rehash`. Returns the old value.
+    pub fn update(&mut self, index: NodeIndex, chunk: Chunk) -> Result<Option<Chunk>> {
+        let old = self.insert(index, chunk)?;
+        self.dirty.insert(index);
+
+        Ok(old)
+    }
+
+    /// Recalculates the ancestors of every dirty node, visiting each affected node only once.
+    ///
+    /// If an ancestor can't be recalculated because a sibling is not loaded, it and all of its
+    /// ancestors are removed so that no stale chunks are left behind.
+    ///
+    /// Returns `Error::InvalidIndex` without recalculating anything if a node is loaded at an
+    /// index with no place in the tree.
+    pub fn rehash(&mut self) -> Result<()> {
+        self.check_indices()?;
+
+        while let Some(index) = self.dirty.iter().next_back().cloned() {
+            self.dirty.remove(&index);
+
+            if index == 0 {
+                continue;
+            }
+
+            let (left, right, parent) = expand_tree_index(index);
+
+            if let (Some(l), Some(r)) = (self.get(left)?, self.get(right)?) {
+                let h = H::hash_children(&l, &r);
+                self.insert(parent, h)?;
+                self.dirty.insert(parent);
+            } else {
+                let mut ancestor = parent;
+
+                loop {
+                    self.remove(ancestor)?;
+                    self.dirty.remove(&ancestor);
+
+                    if ancestor == 0 {
+                        break;
+                    }
+
+                    ancestor = expand_tree_index(ancestor).2;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts every node from `other`. If both contain a node with different chunks, nothing is
+    /// inserted and an error naming the conflicting node closest to the root is returned.
+    pub fn merge<O: NodeStore>(&mut self, other: Backend<H, O>) -> Result<()> {
+        let mut conflict = None;
+
+        for node in other.db.iter() {
+            let (index, chunk) = node?;
+
+            if self.get(index)?.is_some_and(|c| c != chunk) {
+                conflict = Some(conflict.map_or(index, |c: NodeIndex| c.min(index)));
+            }
+        }
+
+        if let Some(index) = conflict {
+            return Err(Error::ChunkConflict(index));
+        }
+
+        for node in other.db.iter() {
+            let (index, chunk) = node?;
+            self.insert(index, chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes every node for which `f` returns `false`.
+    pub fn retain<F: FnMut(NodeIndex) -> bool>(&mut self, mut f: F) -> Result<()> {
+        for index in self.db.indices()? {
+            if !f(index) {
+                self.remove(index)?;
+                self.dirty.remove(&index);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the indices of the nodes whose chunks differ from those in `other`, including the
+    /// nodes which are only loaded in one of them, in increasing order.
+    pub fn diff<O: NodeStore>(&self, other: &Backend<H, O>) -> Result<Vec<NodeIndex>> {
+        let mut ret = vec![];
+
+        for node in self.db.iter() {
+            let (index, chunk) = node?;
+
+            if other.get(index)? != Some(chunk) {
+                ret.push(index);
+            }
+        }
+
+        for index in other.db.indices()? {
+            if !self.contains_node(index)? {
+                ret.push(index);
+            }
+        }
+
+        ret.sort();
+        Ok(ret)
     }
 
     /// Retrieves a vector of loaded node indicies.
-    pub fn nodes(&self) -> Vec<NodeIndex> {
-        self.db.keys().clone().map(|x| x.to_owned()).collect()
+    pub fn nodes(&self) -> Result<Vec<NodeIndex>> {
+        Ok(self.db.indices()?)
     }
 
     /// Returns `true` if the cache contains a chunk for the specified node index.
-    pub fn contains_node(&self, index: NodeIndex) -> bool {
-        self.db.contains_key(&index)
+    pub fn contains_node(&self, index: NodeIndex) -> Result<bool> {
+        Ok(self.db.contains(index)?)
     }
 
     /// Determines if the current merkle tree is valid.
-    pub fn is_valid(&self, root: Vec<u8>) -> bool {
-        for node in self.nodes() {
-            let (left, right, parent) = expand_tree_index(node);
+    pub fn is_valid(&self, root: Chunk) -> bool {
+        self.verify(&root).is_ok()
+    }
 
-            if node > 1 {
-                let left = self.get(left);
-                let right = self.get(right);
-                let parent = self.get(parent);
+    /// Verifies that every loaded node is consistent with its parent and that the tree's root
+    /// matches `root`. Nodes are checked from the bottom of the tree up, so the deepest
+    /// inconsistency is reported.
+    ///
+    /// A missing sibling which is implied to be an all-zero subtree is only reported as
+    /// `Error::SiblingNotLoaded` if its parent does not match.
+    pub fn verify(&self, root: &Chunk) -> Result<()> {
+        self.check_indices()?;
 
-                if let (Some(left), Some(right), Some(parent)) = (left, right, parent) {
-                    if hash_children(&left, &right) != *parent {
-                        return false;
-                    }
-                } else {
-                    return false;
+        let actual = self.get(0)?.ok_or(Error::RootNotLoaded())?;
+        let covered = self.covered_nodes()?;
+
+        let mut nodes = self.nodes()?;
+        nodes.sort_by(|a, b| b.cmp(a));
+
+        for node in nodes {
+            if node == 0 {
+                continue;
+            }
+
+            let (left, right, parent) = expand_tree_index(node);
+            let sibling = if node == left { right } else { left };
+
+            let sibling_chunk = match self.get(sibling)? {
+                Some(chunk) => chunk,
+                None => self
+                    .implied_chunk(sibling, &covered)
+                    .ok_or(Error::SiblingNotLoaded(sibling))?,
+            };
+
+            let expected = self.get(parent)?.ok_or(Error::ChunkNotLoaded(parent))?;
+            let chunk = self.get(node)?.ok_or(Error::ChunkNotLoaded(node))?;
+            let hash = if node == left {
+                H::hash_children(&chunk, &sibling_chunk)
+            } else {
+                H::hash_children(&sibling_chunk, &chunk)
+            };
+
+            if hash != expected {
+                if !self.contains_node(sibling)? {
+                    return Err(Error::SiblingNotLoaded(sibling));
                 }
+
+                return Err(Error::HashMismatch(parent, expected, hash));
             }
         }
 
-        &root == self.get(0).expect("Tree to have root node")
+        if actual == *root {
+            Ok(())
+        } else {
+            Err(Error::RootMismatch(*root, actual))
+        }
     }
 
     /// Inserts missing nodes into the merkle tree that can be generated from existing nodes.
+    ///
+    /// Missing siblings which are implied to be all-zero subtrees are inserted as well.
     pub fn fill(&mut self) -> Result<()> {
-        let mut nodes: Vec<u64> = self.nodes();
-        nodes.sort_by(|a, b| b.cmp(a));
+        self.check_indices()?;
+        self.insert_implied_siblings()?;
+        self.hash_levels(false)
+    }
 
-        let mut position = 0;
-        while position < nodes.len() {
-            let (left, right, parent) = expand_tree_index(nodes[position]);
+    /// Calculates the nodes of the subtree rooted at `root` which is `depth` levels deep, where
+    /// only the first `leaves` leaves have been inserted. The remaining leaves are treated as
+    /// padding and only the roots of the padding subtrees are inserted. Returns the chunk at
+    /// `root`, `Error::ChunkNotLoaded` if one of the first `leaves` leaves is missing or
+    /// `Error::IndexOverflow` if the subtree does not fit in a `NodeIndex`.
+    pub fn hash_subtree(&mut self, root: NodeIndex, depth: u64, leaves: u64) -> Result<Chunk> {
+        let chunk = if leaves == 0 {
+            H::zero_hash(depth)
+        } else if depth == 0 {
+            return self.get(root)?.ok_or(Error::ChunkNotLoaded(root));
+        } else {
+            let (left, right) = root
+                .checked_mul(2)
+                .and_then(|i| Some((i.checked_add(1)?, i.checked_add(2)?)))
+                .ok_or(Error::IndexOverflow())?;
+            let half = 1_u64.checked_shl((depth - 1) as u32).unwrap_or(u64::MAX);
+            let left = self.hash_subtree(left, depth - 1, leaves.min(half))?;
+            let right = self.hash_subtree(right, depth - 1, leaves.saturating_sub(half))?;
+
+            H::hash_children(&left, &right)
+        };
+
+        self.insert(root, chunk)?;
+        Ok(chunk)
+    }
 
-            if self.contains_node(left) && self.contains_node(right) && !self.contains_node(parent)
-            {
-                let h = hash_children(
-                    &self.get(left).ok_or(Error::ChunkNotLoaded(left))?,
-                    &self.get(right).ok_or(Error::ChunkNotLoaded(right))?,
-                );
+    /// Recalculates all intermediate nodes and root using the available leaves.
+    ///
+    /// Missing siblings which are implied to be all-zero subtrees are inserted first.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.check_indices()?;
+        self.dirty.clear();
+        self.insert_implied_siblings()?;
+        self.hash_levels(true)
+    }
 
-                self.insert(parent, h);
-                nodes.push(parent);
+    /// Calculates the parent of every pair of loaded siblings, one level at a time from the
+    /// bottom of the tree up, so that the parents calculated for one level can be used for the
+    /// next. Parents which are already loaded are only recalculated if `overwrite` is `true`.
+    fn hash_levels(&mut self, overwrite: bool) -> Result<()> {
+        // The parents of the loaded nodes, grouped by the depth of the nodes.
+        let mut levels: BTreeMap<u64, BTreeSet<NodeIndex>> = BTreeMap::new();
+
+        for index in self.db.indices()?.into_iter().filter(|i| *i > 0) {
+            levels
+                .entry(index_depth(index))
+                .or_default()
+                .insert(expand_tree_index(index).2);
+        }
+
+        while let Some(depth) = levels.keys().next_back().cloned() {
+            let mut parents = vec![];
+
+            for p in levels.remove(&depth).unwrap_or_default() {
+                if (overwrite || !self.contains_node(p)?)
+                    && self.contains_node(2 * p + 1)?
+                    && self.contains_node(2 * p + 2)?
+                {
+                    parents.push(p);
+                }
             }
 
-            position += 1;
+            let chunks = self.hash_parents(&parents)?;
+
+            for (parent, chunk) in parents.into_iter().zip(chunks) {
+                self.insert(parent, chunk)?;
+
+                if parent > 0 {
+                    levels
+                        .entry(depth - 1)
+                        .or_default()
+                        .insert(expand_tree_index(parent).2);
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub fn refresh(&mut self) -> Result<()> {
-        let mut nodes: Vec<u64> = self.nodes();
-        nodes.sort_by(|a, b| b.cmp(a));
+    /// Hashes the loaded children of each node in `parents`. The nodes are hashed on a thread
+    /// pool, since they are independent of one another.
+    #[cfg(feature = "parallel")]
+    fn hash_parents(&self, parents: &[NodeIndex]) -> Result<Vec<Chunk>> {
+        use rayon::prelude::*;
+
+        let db = &self.db;
+        parents
+            .par_iter()
+            .map(|p| hash_stored_children::<H, S>(db, *p))
+            .collect()
+    }
 
-        let mut position = 0;
-        while nodes[position] > 0 {
-            let (left, right, parent) = expand_tree_index(nodes[position]);
+    /// Hashes the loaded children of each node in `parents`.
+    #[cfg(not(feature = "parallel"))]
+    fn hash_parents(&self, parents: &[NodeIndex]) -> Result<Vec<Chunk>> {
+        parents
+            .iter()
+            .map(|p| hash_stored_children::<H, S>(&self.db, *p))
+            .collect()
+    }
 
-            if self.contains_node(left) && self.contains_node(right) {
-                let h = hash_children(
-                    &self.get(left).ok_or(Error::ChunkNotLoaded(left))?,
-                    &self.get(right).ok_or(Error::ChunkNotLoaded(right))?,
-                );
+    /// Returns `Error::InvalidIndex` if a node is loaded at `NodeIndex::MAX`, which has no
+    /// general index and so no place in a merkle tree. Every other index has a valid family.
+    fn check_indices(&self) -> Result<()> {
+        if self.contains_node(NodeIndex::MAX)? {
+            Err(Error::InvalidIndex(NodeIndex::MAX))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the indices of every loaded node and all of their ancestors.
+    fn covered_nodes(&self) -> Result<HashSet<NodeIndex>> {
+        let mut ret = HashSet::new();
 
-                self.insert(parent, h);
-                nodes.push(parent);
+        for mut index in self.db.indices()? {
+            while ret.insert(index) && index > 0 {
+                index = expand_tree_index(index).2;
             }
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns the chunk of the missing node at `index` if it is implied to be the root of an
+    /// all-zero subtree. Nodes in `covered` have loaded descendants and are never implied.
+    fn implied_chunk(&self, index: NodeIndex, covered: &HashSet<NodeIndex>) -> Option<Chunk> {
+        if covered.contains(&index) {
+            return None;
+        }
 
-            position += 1;
+        (self.zero_subtree_height)(index).map(H::zero_hash)
+    }
+
+    /// Inserts the implied chunk of every missing sibling of a loaded node or of its ancestors.
+    fn insert_implied_siblings(&mut self) -> Result<()> {
+        let covered = self.covered_nodes()?;
+
+        for index in covered.iter().filter(|i| **i > 0) {
+            let sibling = sibling_index(*index);
+
+            if !self.contains_node(sibling)? {
+                if let Some(chunk) = self.implied_chunk(sibling, &covered) {
+                    self.insert(sibling, chunk)?;
+                }
+            }
         }
 
         Ok(())
     }
 }
 
-/// Helper function that appends `right` to `left` and hashes the result.
-pub fn hash_children(left: &[u8], right: &[u8]) -> Vec<u8> {
-    let children: Vec<u8> = left.iter().cloned().chain(right.iter().cloned()).collect();
-    Sha256::digest(&children).as_ref().into()
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones are detached from the log of the original, so their writes are not persisted.
+impl<H: Hasher, S: NodeStore + Clone> Clone for Backend<H, S> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            dirty: self.dirty.clone(),
+            zero_subtree_height: self.zero_subtree_height,
+            log: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Backends whose nodes can not be read are never equal.
+impl<H: Hasher, S: NodeStore> PartialEq for Backend<H, S> {
+    fn eq(&self, other: &Self) -> bool {
+        let nodes = |db: &S| db.iter().collect::<io::Result<BTreeMap<_, _>>>().ok();
+
+        match (nodes(&self.db), nodes(&other.db)) {
+            (Some(a), Some(b)) => a == b && self.dirty == other.dirty,
+            _ => false,
+        }
+    }
+}
+
+/// Hashes the children of `parent`, which must both be stored in `db`.
+fn hash_stored_children<H: Hasher, S: NodeStore>(db: &S, parent: NodeIndex) -> Result<Chunk> {
+    let child = |index| db.get(index)?.ok_or(Error::ChunkNotLoaded(index));
+    Ok(H::hash_children(
+        &child(2 * parent + 1)?,
+        &child(2 * parent + 2)?,
+    ))
 }
 
-impl std::ops::Index<usize> for Backend {
-    type Output = Vec<u8>;
+/// Appends `right` to `left` and hashes the result using SHA-256.
+pub fn hash_children(left: &Chunk, right: &Chunk) -> Chunk {
+    Sha256Hasher::hash_children(left, right)
+}
+
+/// Returns the root of a tree which is `depth` levels deep and whose leaves are all zero chunks,
+/// using SHA-256.
+///
+/// # Panics
+/// Panics if `depth` is greater than the depth of the deepest tree addressable by a `NodeIndex`.
+pub fn zero_hash(depth: u64) -> Chunk {
+    Sha256Hasher::zero_hash(depth)
+}
+
+/// Nodes can be indexed directly when they are kept in memory.
+impl<H: Hasher, S: NodeStore> std::ops::Index<usize> for Backend<H, S>
+where
+    S: for<'a> std::ops::Index<&'a NodeIndex, Output = Chunk>,
+{
+    type Output = Chunk;
 
     fn index(&self, index: usize) -> &Self::Output {
-        self.get(index as u64).expect("node acessible by index")
+        &self.db[&(index as NodeIndex)]
     }
 }
 
@@ -134,36 +552,262 @@ mod tests {
         let mut db = Backend::default();
 
         // leaf nodes
-        db.insert(6, vec![6; BYTES_PER_CHUNK]);
-        db.insert(5, vec![5; BYTES_PER_CHUNK]);
-        db.insert(4, vec![4; BYTES_PER_CHUNK]);
-        db.insert(3, vec![3; BYTES_PER_CHUNK]);
+        db.insert(6, Chunk([6; BYTES_PER_CHUNK])).unwrap();
+        db.insert(5, Chunk([5; BYTES_PER_CHUNK])).unwrap();
+        db.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+        db.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
 
         let two = hash_children(&db[5], &db[6]);
         let one = hash_children(&db[3], &db[4]);
         let root = hash_children(&one, &two);
 
         assert_eq!(db.fill(), Ok(()));
-        assert_eq!(db.is_valid(root), true);
+        assert!(db.is_valid(root));
+
+        // A node without a general index is rejected instead of overflowing
+        db.insert(NodeIndex::MAX, Chunk::default()).unwrap();
+        assert_eq!(db.fill(), Err(Error::InvalidIndex(NodeIndex::MAX)));
+        assert_eq!(db.refresh(), Err(Error::InvalidIndex(NodeIndex::MAX)));
+        assert_eq!(db.verify(&root), Err(Error::InvalidIndex(NodeIndex::MAX)));
     }
     #[test]
     fn is_valid() {
         let mut db = Backend::default();
 
         // leaf nodes
-        db.insert(6, vec![6; BYTES_PER_CHUNK]);
-        db.insert(5, vec![5; BYTES_PER_CHUNK]);
-        db.insert(4, vec![4; BYTES_PER_CHUNK]);
-        db.insert(3, vec![3; BYTES_PER_CHUNK]);
+        db.insert(6, Chunk([6; BYTES_PER_CHUNK])).unwrap();
+        db.insert(5, Chunk([5; BYTES_PER_CHUNK])).unwrap();
+        db.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+        db.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
 
         // intermediate nodes
-        db.insert(2, hash_children(&db[5], &db[6]));
-        db.insert(1, hash_children(&db[3], &db[4]));
+        db.insert(2, hash_children(&db[5], &db[6])).unwrap();
+        db.insert(1, hash_children(&db[3], &db[4])).unwrap();
 
         // root node
         let root = hash_children(&db[1], &db[2]);
-        db.insert(0, root.clone());
+        db.insert(0, root).unwrap();
+
+        assert!(db.is_valid(root));
+    }
+
+    #[test]
+    fn rehash_dirty_nodes() {
+        let mut db = Backend::default();
+
+        // leaf nodes
+        db.insert(6, Chunk([6; BYTES_PER_CHUNK])).unwrap();
+        db.insert(5, Chunk([5; BYTES_PER_CHUNK])).unwrap();
+        db.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+        db.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
+        assert_eq!(db.fill(), Ok(()));
+
+        db.update(3, Chunk([7; BYTES_PER_CHUNK])).unwrap();
+        db.update(6, Chunk([8; BYTES_PER_CHUNK])).unwrap();
+        assert_eq!(db.rehash(), Ok(()));
+
+        let two = hash_children(&db[5], &db[6]);
+        let one = hash_children(&db[3], &db[4]);
+        let root = hash_children(&one, &two);
+
+        assert!(db.is_valid(root));
+
+        // Ancestors which can no longer be calculated are removed
+        db.insert(8, Chunk([8; BYTES_PER_CHUNK])).unwrap();
+        db.update(8, Chunk([9; BYTES_PER_CHUNK])).unwrap();
+        assert_eq!(db.rehash(), Ok(()));
+
+        assert_eq!(db.get(3), Ok(None));
+        assert_eq!(db.get(1), Ok(None));
+        assert_eq!(db.get(0), Ok(None));
+        assert_eq!(db.get(2), Ok(Some(two)));
+
+        // Nodes without a general index are rejected before anything is recalculated
+        db.update(NodeIndex::MAX, Chunk([1; BYTES_PER_CHUNK]))
+            .unwrap();
+        assert_eq!(db.rehash(), Err(Error::InvalidIndex(NodeIndex::MAX)));
+        assert_eq!(db.get(2), Ok(Some(two)));
+    }
+
+    #[test]
+    fn hash_padded_subtree() {
+        let mut db = Backend::default();
+
+        // leaf nodes
+        db.insert(7, Chunk([7; BYTES_PER_CHUNK])).unwrap();
+        db.insert(8, Chunk([8; BYTES_PER_CHUNK])).unwrap();
+        db.insert(9, Chunk([9; BYTES_PER_CHUNK])).unwrap();
+
+        let three = hash_children(&db[7], &db[8]);
+        let four = hash_children(&db[9], &zero_hash(0));
+        let one = hash_children(&three, &four);
+        let root = hash_children(&one, &zero_hash(2));
+
+        assert_eq!(db.hash_subtree(0, 3, 3), Ok(root));
+        assert_eq!(db.get(10), Ok(Some(zero_hash(0))));
+        assert_eq!(db.get(2), Ok(Some(zero_hash(2))));
+        assert_eq!(db.get(5), Ok(None));
+        assert!(db.is_valid(root));
+
+        // Missing leaves and subtrees beyond the last index are errors
+        assert_eq!(
+            Backend::new().hash_subtree(0, 1, 1),
+            Err(Error::ChunkNotLoaded(1))
+        );
+        assert_eq!(
+            db.hash_subtree(NodeIndex::MAX / 2, 1, 1),
+            Err(Error::IndexOverflow())
+        );
+    }
+
+    #[test]
+    fn merge_backends() {
+        let mut a = Backend::default();
+        a.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
+        a.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+
+        let mut b = Backend::default();
+        b.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+        b.insert(2, Chunk([2; BYTES_PER_CHUNK])).unwrap();
+
+        assert_eq!(a.merge(b.clone()), Ok(()));
+        assert_eq!(a.get(2), Ok(Some(Chunk([2; BYTES_PER_CHUNK]))));
+        assert_eq!(a.nodes().unwrap().len(), 3);
+
+        b.insert(1, Chunk([1; BYTES_PER_CHUNK])).unwrap();
+        b.insert(3, Chunk::default()).unwrap();
+        b.insert(2, Chunk::default()).unwrap();
+
+        assert_eq!(a.merge(b), Err(Error::ChunkConflict(2)));
+        assert_eq!(a.get(1), Ok(None));
+    }
+
+    #[test]
+    fn diff_backends() {
+        let mut a = Backend::default();
+        a.insert(1, Chunk([1; BYTES_PER_CHUNK])).unwrap();
+        a.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
+        a.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+
+        let mut b = a.clone();
+        assert_eq!(a.diff(&b), Ok(vec![]));
+
+        b.insert(4, Chunk::default()).unwrap();
+        b.insert(2, Chunk([2; BYTES_PER_CHUNK])).unwrap();
+        b.remove(1).unwrap();
+
+        assert_eq!(a.diff(&b), Ok(vec![1, 2, 4]));
+        assert_eq!(b.diff(&a), Ok(vec![1, 2, 4]));
+    }
+
+    #[test]
+    fn verify_errors() {
+        let mut db = Backend::default();
+
+        // leaf nodes
+        db.insert(6, Chunk([6; BYTES_PER_CHUNK])).unwrap();
+        db.insert(5, Chunk([5; BYTES_PER_CHUNK])).unwrap();
+        db.insert(4, Chunk([4; BYTES_PER_CHUNK])).unwrap();
+        db.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
+
+        assert_eq!(db.verify(&Chunk::default()), Err(Error::RootNotLoaded()));
+        assert!(!db.is_valid(Chunk::default()));
+
+        assert_eq!(db.fill(), Ok(()));
+        let root = db[0];
+
+        assert_eq!(db.verify(&root), Ok(()));
+        assert_eq!(
+            db.verify(&Chunk::default()),
+            Err(Error::RootMismatch(Chunk::default(), root))
+        );
+
+        let one = db[1];
+        db.insert(3, Chunk::default()).unwrap();
+        assert_eq!(
+            db.verify(&root),
+            Err(Error::HashMismatch(
+                1,
+                one,
+                hash_children(&Chunk::default(), &db[4])
+            ))
+        );
+
+        db.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap();
+        db.insert(8, Chunk([8; BYTES_PER_CHUNK])).unwrap();
+        assert_eq!(db.verify(&root), Err(Error::SiblingNotLoaded(7)));
+    }
+
+    #[test]
+    fn implied_zero_subtrees() {
+        use crate::tree_arithmetic::zeroed::relative_depth;
+
+        let mut db: Backend =
+            Backend::with_zero_subtrees(|index| Some(3 - relative_depth(0, index)));
+
+        // leaf nodes
+        db.insert(7, Chunk([7; BYTES_PER_CHUNK])).unwrap();
+        db.insert(8, Chunk([8; BYTES_PER_CHUNK])).unwrap();
+
+        let three = hash_children(&db[7], &db[8]);
+        let one = hash_children(&three, &zero_hash(1));
+        let root = hash_children(&one, &zero_hash(2));
+
+        let mut strict = Backend::new();
+        strict.merge(db.clone()).unwrap();
+        assert_eq!(strict.fill(), Ok(()));
+        assert_eq!(strict.get(0), Ok(None));
+
+        assert_eq!(db.fill(), Ok(()));
+        assert_eq!(db.get(0), Ok(Some(root)));
+        assert_eq!(db.get(4), Ok(Some(zero_hash(1))));
+        assert_eq!(db.get(2), Ok(Some(zero_hash(2))));
+
+        // Implied nodes do not need to be loaded to verify the tree
+        db.retain(|index| index != 4 && index != 2).unwrap();
+        assert_eq!(db.verify(&root), Ok(()));
+
+        db.insert(1, Chunk([1; BYTES_PER_CHUNK])).unwrap();
+        assert_eq!(db.verify(&root), Err(Error::SiblingNotLoaded(4)));
+
+        db.retain(|index| index != 0 && index != 1 && index != 3)
+            .unwrap();
+        assert_eq!(db.refresh(), Ok(()));
+        assert_eq!(db.get(0), Ok(Some(root)));
+    }
+
+    #[test]
+    fn fill_and_refresh_large_tree() {
+        use crate::tree_arithmetic::zeroed::checked_left_most_leaf;
+
+        let depth = 12;
+        let first = checked_left_most_leaf(0, depth).unwrap();
+
+        let mut leaves = Backend::new();
+        for i in 0..(1 << depth) {
+            let mut chunk = Chunk::default();
+            chunk[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            leaves.insert(first + i, chunk).unwrap();
+        }
+
+        let mut expected = leaves.clone();
+        assert!(expected.hash_subtree(0, depth, 1 << depth).is_ok());
+
+        let mut filled = leaves.clone();
+        assert_eq!(filled.fill(), Ok(()));
+        assert_eq!(filled, expected);
+
+        // Stale intermediate nodes are replaced by `refresh`, but not by `fill`
+        let mut stale = expected.clone();
+        for index in (1..first).step_by(7) {
+            stale.insert(index, Chunk([1; BYTES_PER_CHUNK])).unwrap();
+        }
+
+        let mut refreshed = stale.clone();
+        assert_eq!(refreshed.refresh(), Ok(()));
+        assert_eq!(refreshed, expected);
 
-        assert_eq!(db.is_valid(root), true);
+        assert_eq!(stale.fill(), Ok(()));
+        assert_ne!(stale, expected);
     }
 }
@@ -0,0 +1,278 @@
+//! Inspects and verifies `SerializedProof`s of the overlay types compiled into the binary.
+//!
+//! Run `proof help` for usage.
+
+use ethereum_types::U256;
+use proof::tree_arithmetic::zeroed::index_depth;
+use proof::{
+    impl_merkle_overlay_for_container, Chunk, MerkleTreeOverlay, Path, Proof, SerializedProof,
+};
+use serde::de::{self, value::StrDeserializer};
+use serde::Deserialize;
+use ssz::{Decode, Encode};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: proof <command> --type <type> [options] [file]
+
+Reads a SerializedProof from `file`, or from stdin if it is omitted or `-`.
+
+Commands:
+    verify --root <0x..>    Fill the proof and verify it against the root
+    fill                    Fill the proof and write it out
+    extract --path <path>   Fill the proof and write the proof of `path`, e.g. 'b[2]'
+    show                    List the index, depth and chunk of every node
+    types                   List the types which can be selected with --type
+
+Options:
+    --type <type>           The type the proof is for
+    --format <ssz|json>     The encoding of the input, detected if omitted
+    --output <ssz|json>     The encoding of the output, defaults to that of the input
+";
+
+struct Checkpoint;
+impl_merkle_overlay_for_container!(Checkpoint, [epoch: u64, root: U256]);
+
+struct Eth1Data;
+impl_merkle_overlay_for_container!(
+    Eth1Data,
+    [deposit_root: U256, deposit_count: u64, block_hash: U256]
+);
+
+struct BeaconBlockHeader;
+impl_merkle_overlay_for_container!(
+    BeaconBlockHeader,
+    [
+        slot: u64,
+        proposer_index: u64,
+        parent_root: U256,
+        state_root: U256,
+        body_root: U256,
+    ]
+);
+
+/// Defines the types which can be selected with `--type`.
+macro_rules! registry {
+    ($($type: ident),* $(,)*) => {
+        const TYPES: &[&str] = &[$(stringify!($type)),*];
+
+        /// Runs `command` with the type named by its `--type` option.
+        fn dispatch(command: &Command) -> Result<Vec<u8>, String> {
+            match command.ty.as_str() {
+                $(stringify!($type) => run::<$type>(command),)*
+                "" => Err("missing --type, see `proof types`".to_string()),
+                other => Err(format!("unknown type `{}`, see `proof types`", other)),
+            }
+        }
+    };
+}
+
+registry!(BeaconBlockHeader, Checkpoint, Eth1Data);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ssz,
+    Json,
+}
+
+impl Format {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "ssz" => Ok(Format::Ssz),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown format `{}`", s)),
+        }
+    }
+}
+
+/// The parsed command line.
+#[derive(Debug, Default)]
+struct Command {
+    name: String,
+    ty: String,
+    root: Option<String>,
+    path: Option<String>,
+    format: Option<Format>,
+    output: Option<Format>,
+    file: Option<String>,
+}
+
+impl Command {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut ret = Command::default();
+        let mut args = args.iter();
+
+        ret.name = args.next().cloned().unwrap_or_default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+
+            match arg.as_str() {
+                "--type" => ret.ty = value()?,
+                "--root" => ret.root = Some(value()?),
+                "--path" => ret.path = Some(value()?),
+                "--format" => ret.format = Some(Format::parse(&value()?)?),
+                "--output" => ret.output = Some(Format::parse(&value()?)?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ if ret.file.is_none() => ret.file = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Reads the input proof and returns it along with its format.
+    fn read(&self) -> Result<(SerializedProof, Format), String> {
+        let mut bytes = vec![];
+
+        match self.file.as_deref() {
+            None | Some("-") => io::stdin().read_to_end(&mut bytes),
+            Some(file) => std::fs::File::open(file).and_then(|mut f| f.read_to_end(&mut bytes)),
+        }
+        .map_err(|e| format!("failed to read input: {}", e))?;
+
+        let format =
+            self.format
+                .unwrap_or_else(|| match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+                    Some(b'{') => Format::Json,
+                    _ => Format::Ssz,
+                });
+
+        let proof = match format {
+            Format::Ssz => SerializedProof::from_ssz_bytes(&bytes)
+                .map_err(|e| format!("invalid SSZ proof: {:?}", e))?,
+            Format::Json => {
+                serde_json::from_slice(&bytes).map_err(|e| format!("invalid JSON proof: {}", e))?
+            }
+        };
+
+        Ok((proof, format))
+    }
+}
+
+/// Parses a `0x` prefixed hex encoded chunk, as chunks are written in JSON.
+fn parse_chunk(s: &str) -> Result<Chunk, String> {
+    Chunk::deserialize(StrDeserializer::<de::value::Error>::new(s))
+        .map_err(|e| format!("invalid chunk `{}`: {}", s, e))
+}
+
+/// Returns `chunk` as a `0x` prefixed hex string, as chunks are written in JSON.
+fn to_hex(chunk: &Chunk) -> String {
+    match serde_json::to_value(chunk) {
+        Ok(serde_json::Value::String(hex)) => hex,
+        _ => unreachable!("chunks are serialized as strings"),
+    }
+}
+
+/// Encodes `proof` in `format`.
+fn encode(proof: &SerializedProof, format: Format) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Ssz => Ok(proof.as_ssz_bytes()),
+        Format::Json => serde_json::to_string_pretty(proof)
+            .map(|json| format!("{}\n", json).into_bytes())
+            .map_err(|e| format!("failed to encode proof: {}", e)),
+    }
+}
+
+/// Runs `command` for proofs of `T` and returns the bytes to write to stdout.
+fn run<T: MerkleTreeOverlay>(command: &Command) -> Result<Vec<u8>, String> {
+    let (input, format) = command.read()?;
+    let output = command.output.unwrap_or(format);
+
+    let mut p = Proof::<T>::try_from(input).map_err(|e| format!("invalid proof: {:?}", e))?;
+
+    if command.name != "show" {
+        p.fill()
+            .map_err(|e| format!("failed to fill proof: {:?}", e))?;
+    }
+
+    let nodes = || {
+        p.to_serialized()
+            .map_err(|e| format!("failed to read proof: {:?}", e))
+    };
+
+    match command.name.as_str() {
+        "verify" => {
+            let root = parse_chunk(command.root.as_ref().ok_or("missing --root")?)?;
+
+            p.verify(&root)
+                .map_err(|e| format!("verification failed: {:?}", e))?;
+
+            Ok(b"ok\n".to_vec())
+        }
+        "fill" => encode(&nodes()?, output),
+        "extract" => {
+            let path: Path = command
+                .path
+                .as_ref()
+                .ok_or("missing --path")?
+                .parse()
+                .map_err(|e| format!("invalid path: {:?}", e))?;
+
+            let proof = p
+                .extract(path)
+                .map_err(|e| format!("failed to extract path: {:?}", e))?;
+
+            encode(&proof, output)
+        }
+        "show" => {
+            let mut ret = String::new();
+
+            for (index, chunk) in nodes()?.nodes() {
+                let path = T::get_path(index)
+                    .map(|path| format!(" {}", Path::from(path)))
+                    .unwrap_or_default();
+
+                ret += &format!(
+                    "{:>6} {:>3} {}{}\n",
+                    index,
+                    index_depth(index),
+                    to_hex(&chunk),
+                    path
+                );
+            }
+
+            Ok(ret.into_bytes())
+        }
+        other => Err(format!("unknown command `{}`\n\n{}", other, USAGE)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = Command::parse(&args).and_then(|command| match command.name.as_str() {
+        "" | "help" | "--help" | "-h" => Ok(USAGE.as_bytes().to_vec()),
+        "types" => Ok(TYPES
+            .iter()
+            .map(|t| format!("{}\n", t))
+            .collect::<String>()
+            .into_bytes()),
+        _ => dispatch(&command),
+    });
+
+    match result {
+        Ok(bytes) => {
+            let mut stdout = io::stdout();
+
+            if stdout
+                .write_all(&bytes)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("proof: {}", e);
+            process::exit(1);
+        }
+    }
+}
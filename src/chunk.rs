@@ -0,0 +1,114 @@
+use super::BYTES_PER_CHUNK;
+use crate::error::Error;
+use ssz::{Decode, DecodeError, Encode};
+use std::convert::TryFrom;
+use std::ops::{Deref, DerefMut};
+
+/// A single node of a merkle tree.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Chunk(pub [u8; BYTES_PER_CHUNK]);
+
+impl Chunk {
+    /// Creates a chunk that starts with `bytes` and is padded with zeros.
+    ///
+    /// # Panics
+    /// Panics if `bytes` is longer than `BYTES_PER_CHUNK`.
+    pub fn padded(bytes: &[u8]) -> Self {
+        let mut ret = Self::default();
+        ret[..bytes.len()].copy_from_slice(bytes);
+        ret
+    }
+}
+
+impl Deref for Chunk {
+    type Target = [u8; BYTES_PER_CHUNK];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Chunk {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for Chunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; BYTES_PER_CHUNK]> for Chunk {
+    fn from(bytes: [u8; BYTES_PER_CHUNK]) -> Self {
+        Chunk(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for Chunk {
+    type Error = Error;
+
+    /// Returns `Error::SizeMismatch` if `bytes` is not exactly `BYTES_PER_CHUNK` bytes long.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() != BYTES_PER_CHUNK {
+            return Err(Error::SizeMismatch(BYTES_PER_CHUNK, bytes.len()));
+        }
+
+        Ok(Self::padded(bytes))
+    }
+}
+
+/// Chunks are encoded as SSZ `bytes32` values.
+impl Encode for Chunk {
+    fn is_ssz_fixed_len() -> bool {
+        true
+    }
+
+    fn ssz_fixed_len() -> usize {
+        BYTES_PER_CHUNK
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.0);
+    }
+}
+
+impl Decode for Chunk {
+    fn is_ssz_fixed_len() -> bool {
+        true
+    }
+
+    fn ssz_fixed_len() -> usize {
+        BYTES_PER_CHUNK
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != BYTES_PER_CHUNK {
+            return Err(DecodeError::InvalidByteLength {
+                len: bytes.len(),
+                expected: BYTES_PER_CHUNK,
+            });
+        }
+
+        Ok(Self::padded(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_bytes() {
+        let mut expected = [0; BYTES_PER_CHUNK];
+        expected[..3].copy_from_slice(&[1, 2, 3]);
+
+        assert_eq!(Chunk::padded(&[1, 2, 3]), Chunk(expected));
+        assert_eq!(Chunk::try_from(&expected[..]), Ok(Chunk(expected)));
+        assert_eq!(
+            Chunk::try_from(&[1, 2, 3][..]),
+            Err(Error::SizeMismatch(BYTES_PER_CHUNK, 3))
+        );
+    }
+}
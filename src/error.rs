@@ -1,5 +1,8 @@
 use super::NodeIndex;
+use crate::chunk::Chunk;
 use crate::path::PathElement;
+use ssz::DecodeError;
+use std::io;
 
 /// An enum of errors that can occur when interacting with proof.
 #[derive(Debug, PartialEq)]
@@ -12,6 +15,48 @@ pub enum Error {
     ChunkNotLoaded(NodeIndex),
     // Path provided was empty
     EmptyPath(),
+    // The string could not be parsed into a path
+    MalformedPath(String),
+    // The number of chunks provided does not match the number expected (expected, actual)
+    ChunkCountMismatch(usize, usize),
+    // The calculated root does not match the expected root (expected, actual)
+    RootMismatch(Chunk, Chunk),
+    // The size of the value does not match the size of the node (expected, actual)
+    SizeMismatch(usize, usize),
+    // The bytes of the node could not be decoded into the requested type
+    Decode(DecodeError),
+    // The number of items does not match the length of the collection (expected, actual)
+    InvalidLength(usize, usize),
+    // Two proofs contain different chunks for the same node
+    ChunkConflict(NodeIndex),
+    // The root of the tree is not loaded
+    RootNotLoaded(),
+    // The sibling of a loaded node is not loaded
+    SiblingNotLoaded(NodeIndex),
+    // The hash of a node's children does not match the node (index, expected, actual)
+    HashMismatch(NodeIndex, Chunk, Chunk),
+    // The length of the chunks does not match the number of indices (expected, actual)
+    ChunkLengthMismatch(usize, usize),
+    // The same index appears more than once in a proof
+    DuplicateIndex(NodeIndex),
+    // The index can not exist in the merkle tree
+    InvalidIndex(NodeIndex),
+    // The general index of the path does not fit in a `NodeIndex`
+    IndexOverflow(),
+    // The position is not less than the proven length of the list (position, length)
+    IndexBeyondLength(u64, u64),
+    // The list has no values to remove
+    EmptyList(),
+    // The branch is deeper than any tree which can be addressed by a `NodeIndex`
+    InvalidDepth(u64),
+    // Reading or writing the node store failed (kind, message)
+    Io(io::ErrorKind, String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e.kind(), e.to_string())
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,70 @@
+use super::NodeIndex;
+use crate::error::{Error, Result};
+
+/// A 1-indexed generalized index, as used by the specification and other implementations.
+///
+/// The root of the tree is `1` and the children of `n` are `2n` and `2n + 1`. The rest of this
+/// library uses zero-indexed `NodeIndex` values, where the root is `0`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct GeneralizedIndex(NodeIndex);
+
+impl GeneralizedIndex {
+    /// Creates a `GeneralizedIndex` from its 1-indexed value. Returns `Error::InvalidIndex` if
+    /// `index` is `0`.
+    pub fn new(index: NodeIndex) -> Result<Self> {
+        if index == 0 {
+            Err(Error::InvalidIndex(index))
+        } else {
+            Ok(GeneralizedIndex(index))
+        }
+    }
+
+    /// Converts the zero-indexed `index` into a `GeneralizedIndex`. Returns
+    /// `Error::IndexOverflow` if the result does not fit in a `NodeIndex`.
+    pub fn from_node_index(index: NodeIndex) -> Result<Self> {
+        index
+            .checked_add(1)
+            .map(GeneralizedIndex)
+            .ok_or(Error::IndexOverflow())
+    }
+
+    /// Returns the zero-indexed `NodeIndex` of the node.
+    pub fn to_node_index(self) -> NodeIndex {
+        self.0 - 1
+    }
+
+    /// Returns the 1-indexed value of the generalized index.
+    pub fn get(self) -> NodeIndex {
+        self.0
+    }
+}
+
+impl std::fmt::Display for GeneralizedIndex {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_node_indices() {
+        assert_eq!(GeneralizedIndex::new(0), Err(Error::InvalidIndex(0)));
+        assert_eq!(GeneralizedIndex::new(1).map(|g| g.to_node_index()), Ok(0));
+        assert_eq!(GeneralizedIndex::new(12).map(|g| g.to_node_index()), Ok(11));
+
+        assert_eq!(GeneralizedIndex::from_node_index(0).map(|g| g.get()), Ok(1));
+        assert_eq!(
+            GeneralizedIndex::from_node_index(30).map(|g| g.get()),
+            Ok(31)
+        );
+        assert_eq!(
+            GeneralizedIndex::from_node_index(NodeIndex::MAX),
+            Err(Error::IndexOverflow())
+        );
+
+        assert_eq!(GeneralizedIndex::new(5).unwrap().to_string(), "5");
+    }
+}
@@ -0,0 +1,209 @@
+use super::BYTES_PER_CHUNK;
+use crate::chunk::Chunk;
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::sync::OnceLock;
+
+/// The depth of the deepest tree whose nodes can all be addressed by a `NodeIndex`.
+const MAX_TREE_DEPTH: usize = 128;
+
+/// The roots of zeroed trees of each depth, calculated with a single hasher the first time they
+/// are needed.
+///
+/// Each `Hasher` keeps its own table in a `static`, which is read without locking once it has
+/// been initialized.
+#[derive(Debug, Default)]
+pub struct ZeroHashes(OnceLock<Vec<Chunk>>);
+
+impl ZeroHashes {
+    pub const fn new() -> Self {
+        ZeroHashes(OnceLock::new())
+    }
+
+    /// Returns the table, calculating it with `H` if this is the first call.
+    pub fn get<H: Hasher>(&self) -> &[Chunk] {
+        self.0.get_or_init(|| {
+            let mut ret = vec![Chunk::default()];
+
+            while ret.len() <= MAX_TREE_DEPTH {
+                let last = &ret[ret.len() - 1];
+                let h = H::hash_children(last, last);
+                ret.push(h);
+            }
+
+            ret
+        })
+    }
+}
+
+/// Defines the hash function used to calculate the nodes of a merkle tree.
+pub trait Hasher: Clone + Debug + Default + PartialEq + 'static {
+    /// Returns the hash of `data`.
+    fn hash(data: &[u8]) -> Chunk;
+
+    /// Appends `right` to `left` and hashes the result.
+    fn hash_children(left: &Chunk, right: &Chunk) -> Chunk {
+        let mut children = [0; 2 * BYTES_PER_CHUNK];
+        children[..BYTES_PER_CHUNK].copy_from_slice(left.as_ref());
+        children[BYTES_PER_CHUNK..].copy_from_slice(right.as_ref());
+
+        Self::hash(&children)
+    }
+
+    /// Returns the roots of zeroed trees of each depth, from the zero chunk up to the root of
+    /// a tree as deep as the deepest tree addressable by a `NodeIndex`.
+    ///
+    /// Implementations should keep the table in a `static`, so it is only calculated once:
+    ///
+    /// ```ignore
+    /// fn zero_hashes() -> &'static [Chunk] {
+    ///     static ZERO_HASHES: ZeroHashes = ZeroHashes::new();
+    ///     ZERO_HASHES.get::<Self>()
+    /// }
+    /// ```
+    fn zero_hashes() -> &'static [Chunk];
+
+    /// Returns the root of a tree which is `depth` levels deep and whose leaves are all zero
+    /// chunks.
+    ///
+    /// # Panics
+    /// Panics if `depth` is greater than the depth of the deepest tree addressable by a
+    /// `NodeIndex`.
+    fn zero_hash(depth: u64) -> Chunk {
+        Self::zero_hashes()[depth as usize]
+    }
+}
+
+/// The SHA-256 hash function used by SSZ.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sha256Hasher;
+
+impl Hasher for Sha256Hasher {
+    fn hash(data: &[u8]) -> Chunk {
+        let mut ret = Chunk::default();
+        ret.copy_from_slice(&Sha256::digest(data));
+        ret
+    }
+
+    fn zero_hashes() -> &'static [Chunk] {
+        static ZERO_HASHES: ZeroHashes = ZeroHashes::new();
+        ZERO_HASHES.get::<Self>()
+    }
+}
+
+/// The Keccak-256 hash function.
+#[cfg(feature = "keccak")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Keccak256Hasher;
+
+#[cfg(feature = "keccak")]
+impl Hasher for Keccak256Hasher {
+    fn hash(data: &[u8]) -> Chunk {
+        use tiny_keccak::{Hasher as _, Keccak};
+
+        let mut ret = Chunk::default();
+        let mut keccak = Keccak::v256();
+        keccak.update(data);
+        keccak.finalize(&mut *ret);
+
+        ret
+    }
+
+    fn zero_hashes() -> &'static [Chunk] {
+        static ZERO_HASHES: ZeroHashes = ZeroHashes::new();
+        ZERO_HASHES.get::<Self>()
+    }
+}
+
+/// A hash function which XORs the 32-byte chunks of the data together.
+///
+/// It is much cheaper than a real hash function, which makes it useful for tests, but it offers
+/// no security at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct XorHasher;
+
+impl Hasher for XorHasher {
+    fn hash(data: &[u8]) -> Chunk {
+        let mut ret = Chunk::default();
+
+        for chunk in data.chunks(BYTES_PER_CHUNK) {
+            for (r, b) in ret.iter_mut().zip(chunk) {
+                *r ^= b;
+            }
+        }
+
+        ret
+    }
+
+    fn zero_hashes() -> &'static [Chunk] {
+        static ZERO_HASHES: ZeroHashes = ZeroHashes::new();
+        ZERO_HASHES.get::<Self>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{b, s, S};
+    use crate::Proof;
+
+    #[test]
+    fn hash_with_each_hasher() {
+        assert_eq!(
+            XorHasher::hash_children(&Chunk([1; 32]), &Chunk([3; 32])),
+            Chunk([2; BYTES_PER_CHUNK])
+        );
+        assert_eq!(XorHasher::zero_hash(10), Chunk::default());
+
+        assert_eq!(Sha256Hasher::zero_hash(0), Chunk::default());
+        assert_eq!(
+            Sha256Hasher::zero_hash(1).to_vec(),
+            Sha256::digest(&[0; 64]).to_vec()
+        );
+
+        // Each hasher has its own table
+        assert_eq!(Sha256Hasher::zero_hashes().len(), MAX_TREE_DEPTH + 1);
+        assert_ne!(Sha256Hasher::zero_hashes(), XorHasher::zero_hashes());
+    }
+
+    #[cfg(feature = "keccak")]
+    #[test]
+    fn hash_with_keccak() {
+        // The Keccak-256 hash of the empty string
+        let empty = [
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ];
+
+        assert_eq!(Keccak256Hasher::hash(&[]), Chunk(empty));
+        assert_ne!(Keccak256Hasher::zero_hash(1), Sha256Hasher::zero_hash(1));
+    }
+
+    #[test]
+    fn alternative_hashers() {
+        let value = s(vec![1, 2, 3]);
+
+        let sha = Proof::<S>::from_value(&value);
+        let xor = Proof::<S, XorHasher>::from_value(&value);
+        assert_ne!(sha.root(), xor.root());
+
+        // The root is the XOR of every leaf of `S`. `a` cancels out `b[0]` and the length of `b`
+        // cancels out `b[2]`, which leaves `b[1]`.
+        let mut root = Chunk::default();
+        root[16] = 2;
+        assert_eq!(xor.root(), Ok(root));
+
+        // Proofs extracted with one hasher are only valid under the same hasher
+        let extracted = xor.extract(b(2)).unwrap();
+
+        let mut p = Proof::<S, XorHasher>::new(extracted.clone());
+        assert_eq!(p.fill(), Ok(()));
+        assert_eq!(p.verify(&root), Ok(()));
+        assert_eq!(p.get_bytes(b(2)), Ok(3_u128.to_le_bytes().to_vec()));
+
+        let mut p = Proof::<S>::new(extracted);
+        assert_eq!(p.fill(), Ok(()));
+        assert!(p.verify(&root).is_err());
+    }
+}
@@ -15,23 +15,47 @@ pub mod list;
 pub mod reflist;
 
 pub mod backend;
+mod chunk;
 mod error;
+mod generalized_index;
+mod hasher;
 mod merkle_tree_overlay;
 pub mod node;
+mod node_log;
 mod path;
 mod proof;
 mod ser;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod store;
+#[cfg(test)]
+mod test_utils;
 pub mod tree_arithmetic;
 pub mod types;
+mod verify;
 
 pub use crate::backend::hash_children;
-pub use crate::merkle_tree_overlay::{impls, MerkleTreeOverlay};
-pub use crate::path::PathElement;
+pub use crate::chunk::Chunk;
+pub use crate::error::Error;
+pub use crate::generalized_index::GeneralizedIndex;
+#[cfg(feature = "keccak")]
+pub use crate::hasher::Keccak256Hasher;
+pub use crate::hasher::{Hasher, Sha256Hasher, XorHasher, ZeroHashes};
+pub use crate::merkle_tree_overlay::{impls, MerkleTreeOverlay, MerkleTreeValue};
+pub use crate::path::{Path, PathElement};
 pub use crate::proof::Proof;
-pub use crate::ser::SerializedProof;
+pub use crate::ser::{CompactProof, Patch, SerializedProof};
+pub use crate::store::{FileStore, NodeStore};
+pub use crate::verify::{
+    is_valid_merkle_branch, is_valid_merkle_branch_with_hasher, verify_merkle_multiproof,
+    verify_merkle_multiproof_with_hasher,
+};
 
 /// General index for a node in a merkle tree.
-pub type NodeIndex = u64;
+///
+/// Indices are 128 bits wide so that the nodes of deeply nested types, such as large lists
+/// within a list, can still be addressed.
+pub type NodeIndex = u128;
 
 pub const BYTES_PER_CHUNK: usize = 32;
 
@@ -60,4 +84,3 @@ pub mod number {
     make_num!(U4, 4);
     make_num!(U5, 5);
 }
-
@@ -0,0 +1,189 @@
+/// Implements `MerkleTreeOverlay` for an SSZ container with the given fields, in order. Each field
+/// is the root of the subtree of its type, so paths into a field are resolved by the overlay of
+/// its type.
+///
+/// ```ignore
+/// struct Checkpoint;
+/// impl_merkle_overlay_for_container!(Checkpoint, [epoch: u64, root: U256]);
+/// ```
+#[macro_export]
+macro_rules! impl_merkle_overlay_for_container {
+    ($name: ty, [$($field: ident: $type: ty),+ $(,)*]) => {
+        impl $crate::MerkleTreeOverlay for $name {
+            fn height() -> u64 {
+                let fields = [$(stringify!($field)),+];
+                fields.len().next_power_of_two().trailing_zeros() as u64
+            }
+
+            fn max_depth() -> u64 {
+                let depths = [$(<$type as $crate::MerkleTreeOverlay>::max_depth()),+];
+                Self::height() + depths.iter().cloned().max().unwrap_or(0)
+            }
+
+            fn zero_subtree_height(index: $crate::NodeIndex) -> Option<u64> {
+                use $crate::tree_arithmetic::zeroed::{
+                    checked_general_index_to_subtree, checked_left_most_leaf,
+                };
+
+                let heights: &[fn($crate::NodeIndex) -> Option<u64>] =
+                    &[$(<$type as $crate::MerkleTreeOverlay>::zero_subtree_height),+];
+                let first = checked_left_most_leaf(0, Self::height())?;
+
+                heights.iter().enumerate().find_map(|(position, height)| {
+                    let root = first + position as $crate::NodeIndex;
+                    height(checked_general_index_to_subtree(root, index)?)
+                })
+            }
+
+            fn min_repr_size() -> u64 {
+                32
+            }
+
+            fn is_list() -> bool {
+                false
+            }
+
+            fn get_node(
+                path: Vec<$crate::PathElement>,
+            ) -> Result<$crate::node::Node, $crate::Error> {
+                use $crate::tree_arithmetic::zeroed::{
+                    checked_left_most_leaf, checked_subtree_index_to_general,
+                };
+                use $crate::{Error, MerkleTreeOverlay, NodeIndex, PathElement};
+
+                let fields = [$(stringify!($field)),+];
+                let first = path.first().ok_or(Error::EmptyPath())?;
+                let position = fields
+                    .iter()
+                    .position(|field| PathElement::from_ident_str(*field) == *first)
+                    .ok_or_else(|| Error::InvalidPath(first.clone()))?;
+
+                let index = checked_left_most_leaf(0, Self::height())
+                    .ok_or(Error::IndexOverflow())?
+                    + position as NodeIndex;
+
+                if path.len() == 1 {
+                    let heights = [$(<$type as MerkleTreeOverlay>::height()),+];
+                    let lists = [$(<$type as MerkleTreeOverlay>::is_list()),+];
+                    let sizes = [$(<$type as MerkleTreeOverlay>::min_repr_size()),+];
+                    let packed = heights[position] == 0 && !lists[position];
+
+                    Ok($crate::node::Node {
+                        ident: first.clone(),
+                        index,
+                        size: if packed { sizes[position] as u8 } else { 0 },
+                        offset: 0,
+                        height: heights[position],
+                        is_list: lists[position],
+                    })
+                } else {
+                    let nodes: &[fn(Vec<PathElement>) -> Result<$crate::node::Node, Error>] =
+                        &[$(<$type as MerkleTreeOverlay>::get_node),+];
+
+                    let node = nodes[position](path[1..].to_vec())?;
+                    let general = checked_subtree_index_to_general(index, node.index)
+                        .ok_or(Error::IndexOverflow())?;
+
+                    Ok($crate::impls::replace_index(node, general))
+                }
+            }
+
+            fn get_path(index: $crate::NodeIndex) -> Option<Vec<$crate::PathElement>> {
+                use $crate::tree_arithmetic::zeroed::{
+                    checked_general_index_to_subtree, checked_left_most_leaf,
+                };
+                use $crate::{MerkleTreeOverlay, NodeIndex, PathElement};
+
+                let fields = [$(stringify!($field)),+];
+                let depths = [$(<$type as MerkleTreeOverlay>::max_depth()),+];
+
+                // The root of a single field is also the root of the container
+                if index == 0 && (Self::height() > 0 || depths[0] == 0) {
+                    return Some(vec![]);
+                }
+
+                let paths: &[fn(NodeIndex) -> Option<Vec<PathElement>>] =
+                    &[$(<$type as MerkleTreeOverlay>::get_path),+];
+                let first = checked_left_most_leaf(0, Self::height())?;
+
+                let (position, subtree) = (0..fields.len()).find_map(|position| {
+                    let root = first + position as NodeIndex;
+                    Some((position, checked_general_index_to_subtree(root, index)?))
+                })?;
+
+                let mut ret = vec![PathElement::from_ident_str(fields[position])];
+                ret.extend(paths[position](subtree)?);
+                Some(ret)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::{Path, PathElement};
+    use crate::types::VariableList;
+    use crate::{Error, MerkleTreeOverlay};
+    use ethereum_types::U256;
+    use typenum::U8;
+
+    struct Checkpoint;
+    impl_merkle_overlay_for_container!(Checkpoint, [epoch: u64, root: U256]);
+
+    struct State;
+    impl_merkle_overlay_for_container!(
+        State,
+        [
+            slot: u64,
+            finalized: Checkpoint,
+            balances: VariableList<u64, U8>,
+        ]
+    );
+
+    #[test]
+    fn container_overlay() {
+        let path = |s: &str| s.parse::<Path>().unwrap().into();
+
+        assert_eq!(Checkpoint::height(), 1);
+        assert_eq!(State::height(), 2);
+        assert_eq!(State::max_depth(), 2 + VariableList::<u64, U8>::max_depth());
+
+        let node = Checkpoint::get_node(path("epoch")).unwrap();
+        assert_eq!((node.index, node.size, node.height), (1, 8, 0));
+
+        let node = State::get_node(path("finalized")).unwrap();
+        assert_eq!((node.index, node.size, node.height), (4, 0, 1));
+        assert_eq!(State::get_node(path("finalized.root")).unwrap().index, 10);
+
+        let node = State::get_node(path("balances")).unwrap();
+        assert!(node.is_list);
+        assert_eq!(State::get_node(path("balances.len")).unwrap().index, 12);
+
+        for s in &[
+            "slot",
+            "finalized",
+            "finalized.root",
+            "balances[4]",
+            "balances.len",
+        ] {
+            let index = State::get_node(path(s)).unwrap().index;
+            assert_eq!(State::get_path(index), Some(path(s)), "{}", s);
+        }
+
+        // Padding and intermediate nodes have no path
+        assert_eq!(State::get_path(6), None);
+        assert_eq!(State::get_path(1), None);
+
+        // The root of a container with a single nested field maps to the field
+        struct Wrapper;
+        impl_merkle_overlay_for_container!(Wrapper, [finalized: Checkpoint]);
+        assert_eq!(Wrapper::get_path(0), Some(path("finalized")));
+        assert_eq!(Wrapper::get_path(1), Some(path("finalized.epoch")));
+
+        assert_eq!(
+            State::get_node(path("finalized.slot")),
+            Err(Error::InvalidPath(PathElement::from_ident_str("slot")))
+        );
+        assert_eq!(State::get_node(vec![]), Err(Error::EmptyPath()));
+    }
+}
@@ -1,8 +1,15 @@
-use super::MerkleTreeOverlay;
+use super::{MerkleTreeOverlay, MerkleTreeValue};
+use crate::backend::Backend;
+use crate::chunk::Chunk;
 use crate::error::{Error, Result};
+use crate::hasher::Hasher;
 use crate::node::Node;
 use crate::path::PathElement;
-use crate::tree_arithmetic::zeroed::{left_most_leaf, subtree_index_to_general};
+use crate::store::NodeStore;
+use crate::tree_arithmetic::zeroed::{
+    checked_general_index_to_subtree, checked_left_most_leaf, checked_subtree_index_to_general,
+    is_in_subtree, relative_depth, root_from_depth,
+};
 use crate::tree_arithmetic::{log_base_two, next_power_of_two};
 use crate::types::{FixedVector, VariableList};
 use crate::{NodeIndex, BYTES_PER_CHUNK};
@@ -16,6 +23,18 @@ macro_rules! impl_merkle_overlay_for_basic_type {
                 0
             }
 
+            fn max_depth() -> u64 {
+                0
+            }
+
+            fn zero_subtree_height(index: NodeIndex) -> Option<u64> {
+                if index == 0 {
+                    Some(0)
+                } else {
+                    None
+                }
+            }
+
             fn min_repr_size() -> u64 {
                 ($bit_size / 8) as u64
             }
@@ -38,6 +57,14 @@ macro_rules! impl_merkle_overlay_for_basic_type {
                     Err(Error::InvalidPath(path[0].clone()))
                 }
             }
+
+            fn get_path(index: NodeIndex) -> Option<Vec<PathElement>> {
+                if index == 0 {
+                    Some(vec![])
+                } else {
+                    None
+                }
+            }
         }
     };
 }
@@ -58,6 +85,7 @@ impl_merkle_overlay_for_basic_type!(usize, std::mem::size_of::<usize>());
 ///
 /// Below is a visual representation of the merkle tree for variable length Lists:
 ///
+/// ```text
 ///             root
 ///           /      \
 ///      data_root   len
@@ -65,22 +93,24 @@ impl_merkle_overlay_for_basic_type!(usize, std::mem::size_of::<usize>());
 ///       *     *           <= intermediate nodes
 ///      / \   / \
 ///     x   x x   x         <= leaf nodes
+/// ```
 ///
 /// And a visual representation of the merkle tree for fixed length Vectors:
 ///
+/// ```text
 ///             root(0)
 ///             /     \
 ///            *       *    <= intermediate nodes
 ///           / \     / \
 ///          x   x   x   x  <= leaf nodes
-
+/// ```
 macro_rules! impl_merkle_overlay_for_collection_type {
     ($type: ident, $is_variable_length: expr) => {
         impl<T: MerkleTreeOverlay, N: Unsigned> MerkleTreeOverlay for $type<T, N> {
             fn height() -> u64 {
                 let items_per_chunk = BYTES_PER_CHUNK as u64 / T::min_repr_size();
                 // TODO: what if division is 0?
-                let num_leaves = next_power_of_two(N::to_u64() / items_per_chunk);
+                let num_leaves = next_power_of_two((N::to_u64() / items_per_chunk).into());
                 let data_tree_height = log_base_two(num_leaves);
 
                 if $is_variable_length {
@@ -91,6 +121,32 @@ macro_rules! impl_merkle_overlay_for_collection_type {
                 }
             }
 
+            fn max_depth() -> u64 {
+                Self::height() + T::max_depth()
+            }
+
+            fn zero_subtree_height(index: NodeIndex) -> Option<u64> {
+                let depth = relative_depth(0, index);
+                let height = Self::height();
+
+                if $is_variable_length && (index == 0 || is_in_subtree(2, index)) {
+                    // The root of a list is never all-zero, and the length is a single chunk.
+                    if index == 2 {
+                        Some(0)
+                    } else {
+                        None
+                    }
+                } else if depth <= height {
+                    Some(height - depth)
+                } else if BYTES_PER_CHUNK as u64 / T::min_repr_size() > 1 {
+                    // Packed values do not have a subtree of their own.
+                    None
+                } else {
+                    let leaf = root_from_depth(index, depth - height);
+                    T::zero_subtree_height(checked_general_index_to_subtree(leaf, index)?)
+                }
+            }
+
             fn min_repr_size() -> u64 {
                 if Self::height() > 0 {
                     32
@@ -114,9 +170,10 @@ macro_rules! impl_merkle_overlay_for_collection_type {
                             return Err(Error::IndexOutOfBounds(*position));
                         }
 
-                        let first_leaf = left_most_leaf(0, Self::height() as u64);
+                        let first_leaf = checked_left_most_leaf(0, Self::height())
+                            .ok_or(Error::IndexOverflow())?;
                         let items_per_chunk = (BYTES_PER_CHUNK as u64 / T::min_repr_size()) as u64;
-                        let leaf_index = first_leaf + (position / items_per_chunk);
+                        let leaf_index = first_leaf + NodeIndex::from(position / items_per_chunk);
 
                         // If the path terminates here, return the node in the current tree.
                         if path.len() == 1 {
@@ -124,7 +181,7 @@ macro_rules! impl_merkle_overlay_for_collection_type {
 
                             Ok(Node {
                                 ident: path[0].clone(),
-                                index: (1 << Self::height()) + (position / items_per_chunk) - 1,
+                                index: leaf_index,
                                 size: T::min_repr_size() as u8,
                                 offset: ((position % items_per_chunk) * T::min_repr_size()) as u8,
                                 height: T::height(),
@@ -133,10 +190,12 @@ macro_rules! impl_merkle_overlay_for_collection_type {
 
                         // If the path does not terminate, recursively call the child `T` to
                         // continue matching the path. Translate the child's return index to
-                        // the current general index space.
+                        // the current general index space. Deeply nested types can produce
+                        // indices which do not fit in a `NodeIndex`, so the translation is checked.
                         } else {
                             let node = T::get_node(path[1..].to_vec())?;
-                            let index = subtree_index_to_general(leaf_index, node.index);
+                            let index = checked_subtree_index_to_general(leaf_index, node.index)
+                                .ok_or(Error::IndexOverflow())?;
 
                             Ok(replace_index(node.clone(), index))
                         }
@@ -161,6 +220,34 @@ macro_rules! impl_merkle_overlay_for_collection_type {
                     None => Err(Error::EmptyPath()),
                 }
             }
+
+            fn get_path(index: NodeIndex) -> Option<Vec<PathElement>> {
+                let depth = relative_depth(0, index);
+                let height = Self::height();
+
+                // The root of a vector with a single composite value is also the root of the value
+                if index == 0 && (height > 0 || T::max_depth() == 0) {
+                    return Some(vec![]);
+                } else if $is_variable_length && index == 2 {
+                    return Some(vec![PathElement::from_ident_str("len")]);
+                } else if depth < height || ($is_variable_length && !is_in_subtree(1, index)) {
+                    return None;
+                }
+
+                // Find the leaf containing the node and the first value packed into that leaf.
+                let items_per_chunk = BYTES_PER_CHUNK as u64 / T::min_repr_size();
+                let leaf = root_from_depth(index, depth - height);
+                let position = (leaf - checked_left_most_leaf(0, height)?) as u64 * items_per_chunk;
+
+                if position >= N::to_u64() || (depth > height && items_per_chunk > 1) {
+                    return None;
+                }
+
+                let mut ret = vec![PathElement::Index(position)];
+                ret.extend(T::get_path(checked_general_index_to_subtree(leaf, index)?)?);
+
+                Some(ret)
+            }
         }
     };
 }
@@ -168,6 +255,112 @@ macro_rules! impl_merkle_overlay_for_collection_type {
 impl_merkle_overlay_for_collection_type!(VariableList, true);
 impl_merkle_overlay_for_collection_type!(FixedVector, false);
 
+macro_rules! impl_merkle_value_for_basic_type {
+    ($type: ident, $to_bytes: expr) => {
+        impl MerkleTreeValue for $type {
+            fn merkleize<H: Hasher, S: NodeStore>(
+                &self,
+                db: &mut Backend<H, S>,
+                root: NodeIndex,
+            ) -> Result<()> {
+                db.insert(root, Chunk::padded(&self.packed_bytes::<H>()?))?;
+                Ok(())
+            }
+
+            fn packed_bytes<H: Hasher>(&self) -> Result<Vec<u8>> {
+                let mut bytes: Vec<u8> = $to_bytes(self);
+                bytes.truncate(Self::min_repr_size() as usize);
+                Ok(bytes)
+            }
+        }
+    };
+}
+
+impl_merkle_value_for_basic_type!(bool, |v: &bool| vec![*v as u8]);
+impl_merkle_value_for_basic_type!(u8, |v: &u8| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(u16, |v: &u16| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(u32, |v: &u32| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(u64, |v: &u64| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(u128, |v: &u128| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(usize, |v: &usize| v.to_le_bytes().to_vec());
+impl_merkle_value_for_basic_type!(U256, |v: &U256| {
+    let mut bytes = vec![0; 32];
+    v.to_little_endian(&mut bytes);
+    bytes
+});
+
+/// Implements the `MerkleTreeValue` trait for SSZ Vector and List types, following the same tree
+/// structure as `impl_merkle_overlay_for_collection_type`.
+macro_rules! impl_merkle_value_for_collection_type {
+    ($type: ident, $is_variable_length: expr) => {
+        impl<T: MerkleTreeValue, N: Unsigned> MerkleTreeValue for $type<T, N> {
+            fn merkleize<H: Hasher, S: NodeStore>(
+                &self,
+                db: &mut Backend<H, S>,
+                root: NodeIndex,
+            ) -> Result<()> {
+                let items_per_chunk = (BYTES_PER_CHUNK as u64 / T::min_repr_size()) as usize;
+
+                // Lists store their data in the left subtree and their length in the right.
+                let (data_root, data_tree_height) = if $is_variable_length {
+                    (1, Self::height() - 1)
+                } else {
+                    (0, Self::height())
+                };
+
+                let first_leaf = checked_left_most_leaf(data_root, data_tree_height)
+                    .ok_or(Error::IndexOverflow())?;
+                let general = |index: NodeIndex| {
+                    checked_subtree_index_to_general(root, index).ok_or(Error::IndexOverflow())
+                };
+
+                for (i, items) in self.chunks(items_per_chunk).enumerate() {
+                    let leaf = general(first_leaf + i as NodeIndex)?;
+
+                    if items_per_chunk == 1 {
+                        items[0].merkleize(db, leaf)?;
+                    } else {
+                        let mut bytes = vec![];
+                        for item in items {
+                            bytes.extend(item.packed_bytes::<H>()?);
+                        }
+                        db.insert(leaf, Chunk::padded(&bytes))?;
+                    }
+                }
+
+                let leaves = self.len().div_ceil(items_per_chunk);
+                let data = db.hash_subtree(general(data_root)?, data_tree_height, leaves as u64)?;
+
+                if $is_variable_length {
+                    let len = Chunk::padded(&(self.len() as u64).to_le_bytes());
+
+                    db.insert(general(2)?, len)?;
+                    db.insert(root, H::hash_children(&data, &len))?;
+                }
+
+                Ok(())
+            }
+
+            fn packed_bytes<H: Hasher>(&self) -> Result<Vec<u8>> {
+                if Self::height() > 0 {
+                    let mut db = Backend::<H>::with_hasher();
+                    self.merkleize(&mut db, 0)?;
+                    Ok(db[0].to_vec())
+                } else {
+                    let mut bytes = vec![];
+                    for item in self.iter() {
+                        bytes.extend(item.packed_bytes::<H>()?);
+                    }
+                    Ok(bytes)
+                }
+            }
+        }
+    };
+}
+
+impl_merkle_value_for_collection_type!(VariableList, true);
+impl_merkle_value_for_collection_type!(FixedVector, false);
+
 /// Returns a copy of `node` with all its index values changed to `index`.
 pub fn replace_index(node: Node, index: NodeIndex) -> Node {
     Node {
@@ -183,6 +376,8 @@ pub fn replace_index(node: Node, index: NodeIndex) -> Node {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::hash_children;
+    use crate::hasher::Sha256Hasher;
     use typenum::{U1, U16, U2, U32, U4, U8};
 
     #[test]
@@ -204,6 +399,8 @@ mod tests {
         //              +--------------- leaves
         type T = VariableList<U256, U8>;
 
+        assert_eq!(T::max_depth(), 4);
+
         // TESTING LENGTH NODE
         assert_eq!(
             T::get_node(vec![PathElement::from_ident_str("len")]),
@@ -399,7 +596,7 @@ mod tests {
                 T::get_node(vec![PathElement::Index(i - 7)]),
                 Ok(Node {
                     ident: PathElement::Index(i - 7),
-                    index: i,
+                    index: i.into(),
                     size: 32,
                     offset: 0,
                     height: 0,
@@ -481,7 +678,7 @@ mod tests {
                 T::get_node(vec![PathElement::Index(0), PathElement::Index(i)]),
                 Ok(Node {
                     ident: PathElement::Index(i),
-                    index: i + 1,
+                    index: (i + 1).into(),
                     size: 32,
                     offset: 0,
                     height: 4,
@@ -498,7 +695,7 @@ mod tests {
                     ]),
                     Ok(Node {
                         ident: PathElement::Index(j),
-                        index: j + 31 + (i * 16),
+                        index: (j + 31 + (i * 16)).into(),
                         size: 32,
                         offset: 0,
                         height: 0,
@@ -534,4 +731,165 @@ mod tests {
             Err(Error::IndexOutOfBounds(16))
         );
     }
+
+    #[test]
+    fn zero_subtree_heights() {
+        type T = VariableList<u128, U8>;
+
+        assert_eq!(T::zero_subtree_height(0), None);
+        assert_eq!(T::zero_subtree_height(1), Some(2));
+        assert_eq!(T::zero_subtree_height(2), Some(0));
+        assert_eq!(T::zero_subtree_height(4), Some(1));
+        assert_eq!(T::zero_subtree_height(10), Some(0));
+        assert_eq!(T::zero_subtree_height(21), None);
+
+        // Merkle structure for `FixedVector<VariableList<U256, U2>, U2>`
+        //
+        //              0
+        //          /       \
+        //         1         2          <= list roots
+        //       /   \     /   \
+        //      3     4   5     6       <= data roots and lengths
+        //     / \       / \
+        //    7   8     11  12          <= leaves
+        type U = FixedVector<VariableList<U256, U2>, U2>;
+
+        assert_eq!(U::zero_subtree_height(0), Some(1));
+        assert_eq!(U::zero_subtree_height(1), Some(0));
+        assert_eq!(U::zero_subtree_height(3), Some(1));
+        assert_eq!(U::zero_subtree_height(6), Some(0));
+        assert_eq!(U::zero_subtree_height(12), Some(0));
+        assert_eq!(U::zero_subtree_height(14), None);
+    }
+
+    #[test]
+    fn node_paths() {
+        let ident = PathElement::from_ident_str;
+
+        // Two `u128`s are packed into each leaf, so leaves map to the first of them
+        type T = VariableList<u128, U8>;
+
+        assert_eq!(T::get_path(0), Some(vec![]));
+        assert_eq!(T::get_path(2), Some(vec![ident("len")]));
+        assert_eq!(T::get_path(3), None);
+        assert_eq!(T::get_path(7), Some(vec![PathElement::Index(0)]));
+        assert_eq!(T::get_path(10), Some(vec![PathElement::Index(6)]));
+        assert_eq!(T::get_path(11), None);
+
+        // See `zero_subtree_heights` for the structure of `U`
+        type U = FixedVector<VariableList<U256, U2>, U2>;
+
+        assert_eq!(U::get_path(2), Some(vec![PathElement::Index(1)]));
+        assert_eq!(U::get_path(3), None);
+        assert_eq!(
+            U::get_path(6),
+            Some(vec![PathElement::Index(1), ident("len")])
+        );
+        assert_eq!(
+            U::get_path(12),
+            Some(vec![PathElement::Index(1), PathElement::Index(1)])
+        );
+        assert_eq!(U::get_path(14), None);
+
+        let path = vec![PathElement::Index(1), PathElement::Index(0)];
+        assert_eq!(
+            U::get_path(U::get_node(path.clone()).unwrap().index),
+            Some(path)
+        );
+    }
+
+    #[test]
+    fn merkleize_variable_list() {
+        type T = VariableList<u128, U8>;
+
+        let list = T::new(vec![1, 2, 3]).unwrap();
+        let mut db = Backend::new();
+        assert_eq!(list.merkleize(&mut db, 0), Ok(()));
+
+        let mut leaf = Chunk::default();
+        leaf[0] = 1;
+        leaf[16] = 2;
+        assert_eq!(db.get(7), Ok(Some(leaf)));
+
+        let mut leaf = Chunk::default();
+        leaf[0] = 3;
+        assert_eq!(db.get(8), Ok(Some(leaf)));
+
+        let mut len = Chunk::default();
+        len[0] = 3;
+        assert_eq!(db.get(2), Ok(Some(len)));
+
+        let three = hash_children(&db[7], &db[8]);
+        let data_root = hash_children(&three, &hash_children(&Chunk::default(), &Chunk::default()));
+        let root = hash_children(&data_root, &len);
+
+        assert_eq!(db.get(0), Ok(Some(root)));
+        assert!(db.is_valid(root));
+
+        // Empty lists are only made up of the data root and the length
+        let mut db = Backend::new();
+        assert_eq!(T::default().merkleize(&mut db, 0), Ok(()));
+        assert_eq!(db.nodes().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn merkleize_nested_fixed_vector() {
+        type T = FixedVector<FixedVector<U256, U2>, U2>;
+
+        let inner = |a: u64, b: u64| {
+            FixedVector::<U256, U2>::new(vec![U256::from(a), U256::from(b)]).unwrap()
+        };
+        let vector = T::new(vec![inner(1, 2), inner(3, 4)]).unwrap();
+
+        let mut db = Backend::new();
+        assert_eq!(vector.merkleize(&mut db, 0), Ok(()));
+
+        for (i, index) in (3..7).enumerate() {
+            let mut leaf = Chunk::default();
+            leaf[0] = i as u8 + 1;
+            assert_eq!(db.get(index), Ok(Some(leaf)));
+        }
+
+        let root = hash_children(
+            &hash_children(&db[3], &db[4]),
+            &hash_children(&db[5], &db[6]),
+        );
+        assert_eq!(db.get(0), Ok(Some(root)));
+        assert_eq!(vector.packed_bytes::<Sha256Hasher>(), Ok(root.to_vec()));
+
+        assert_eq!(
+            T::new(vec![inner(1, 2)]).err(),
+            Some(Error::InvalidLength(2, 1))
+        );
+    }
+
+    #[test]
+    fn wide_nested_variable_list() {
+        use typenum::U1099511627776;
+
+        type Inner = VariableList<U256, U1099511627776>;
+        type T = VariableList<VariableList<Inner, U1099511627776>, U1099511627776>;
+
+        // The 1-indexed general index of the last element in each list, 41 levels below the root
+        // of the list.
+        let last: NodeIndex = 3 * (1 << 40) - 1;
+        let offset = last - (1 << 41);
+
+        assert_eq!(Inner::height(), 41);
+        assert_eq!(T::max_depth(), 123);
+
+        let path = vec![PathElement::Index(2_u64.pow(40) - 1); 3];
+        let node = T::get_node(path).unwrap();
+
+        assert_eq!(node.index, (last << 82) + (offset << 41) + offset - 1);
+        assert!(node.index > NodeIndex::from(u64::MAX));
+
+        // A fourth level of nesting does not fit in a `NodeIndex`
+        type U = VariableList<T, U1099511627776>;
+
+        assert_eq!(
+            U::get_node(vec![PathElement::Index(0); 4]),
+            Err(Error::IndexOverflow())
+        );
+    }
 }
@@ -1,8 +1,13 @@
+mod container;
 pub mod impls;
 
+use crate::backend::Backend;
 use crate::error::Result;
+use crate::hasher::Hasher;
 use crate::node::Node;
 use crate::path::PathElement;
+use crate::store::NodeStore;
+use crate::NodeIndex;
 
 /// Defines an interface for interacting with `Proof`s via `Path`s.
 pub trait MerkleTreeOverlay {
@@ -16,9 +21,36 @@ pub trait MerkleTreeOverlay {
     /// Returns the `Node` coresponding to the given `path`.
     fn get_node(path: Vec<PathElement>) -> Result<Node>;
 
+    /// Returns the path of the value whose chunk is the node at `index`, which is the inverse of
+    /// `get_node`. The root of the tree has an empty path.
+    ///
+    /// Returns `None` for intermediate nodes, for nodes outside of the tree and for types that
+    /// do not support the mapping. A chunk which packs several values maps to the path of the
+    /// first of them. A node which is the root of nested values, such as the root of a container
+    /// with a single composite field, maps to the path of the deepest of them.
+    fn get_path(_index: NodeIndex) -> Option<Vec<PathElement>> {
+        None
+    }
+
     /// Returns the height of the merkle tree.
     fn height() -> u64;
 
+    /// Returns the depth of the deepest node that can exist in the merkle tree, including the
+    /// trees of any nested types.
+    ///
+    /// Proofs are rejected on load if they contain a node below this depth. For a container, this
+    /// is its `height` plus the greatest `max_depth` of its fields.
+    fn max_depth() -> u64;
+
+    /// Returns the height of the all-zero subtree rooted at `index`, which is the number of levels
+    /// between the node and the chunks of the merkle tree containing it, or `None` if the node
+    /// can not be implied to be an all-zero subtree.
+    ///
+    /// This allows proofs to omit the padding of lists and vectors.
+    fn zero_subtree_height(_index: NodeIndex) -> Option<u64> {
+        None
+    }
+
     /// Returns the minimum number of bytes needed to represent the type's value.
     fn min_repr_size() -> u64;
 
@@ -29,3 +61,26 @@ pub trait MerkleTreeOverlay {
     /// lists.
     fn is_list() -> bool;
 }
+
+/// Defines an interface for merkleizing concrete values of a `MerkleTreeOverlay` type.
+pub trait MerkleTreeValue: MerkleTreeOverlay {
+    /// Inserts the chunks of the value's merkle tree into `db`, where `root` is the general index
+    /// of the value's root node.
+    ///
+    /// Subtrees that only contain padding are represented by their root chunk. Returns
+    /// `Error::IndexOverflow` if a node of the tree does not fit in a `NodeIndex`.
+    fn merkleize<H: Hasher, S: NodeStore>(
+        &self,
+        db: &mut Backend<H, S>,
+        root: NodeIndex,
+    ) -> Result<()>;
+
+    /// Returns the bytes used to represent the value when it is packed into a chunk.
+    ///
+    /// By default this is the root chunk of the value's merkle tree, calculated with `H`.
+    fn packed_bytes<H: Hasher>(&self) -> Result<Vec<u8>> {
+        let mut db = Backend::<H>::with_hasher();
+        self.merkleize(&mut db, 0)?;
+        Ok(db.get(0)?.map(|chunk| chunk.to_vec()).unwrap_or_default())
+    }
+}
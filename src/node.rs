@@ -3,7 +3,9 @@ use crate::path::PathElement;
 
 /// Represents any valid node value.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::index"))]
     pub index: NodeIndex,
     pub ident: PathElement,
     pub size: u8,
@@ -0,0 +1,412 @@
+use super::{NodeIndex, BYTES_PER_CHUNK};
+use crate::chunk::Chunk;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+/// The bytes at the start of every log.
+const MAGIC: &[u8; 8] = b"PROOFLOG";
+
+const INDEX_SIZE: usize = size_of::<NodeIndex>();
+const CHECKSUM_SIZE: usize = 8;
+const RECORD_SIZE: usize = 1 + INDEX_SIZE + BYTES_PER_CHUNK + CHECKSUM_SIZE;
+
+/// A single entry of the log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Record {
+    Insert(NodeIndex, Chunk),
+    Remove(NodeIndex),
+    // Marks a consistent state of the nodes (number of nodes, root or an empty chunk)
+    Commit(NodeIndex, Chunk),
+}
+
+impl Record {
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let (tag, index, chunk) = match *self {
+            Record::Insert(index, chunk) => (0, index, chunk),
+            Record::Remove(index) => (1, index, Chunk::default()),
+            Record::Commit(count, root) => (2, count, root),
+        };
+
+        let mut ret = [0; RECORD_SIZE];
+        ret[0] = tag;
+        ret[1..1 + INDEX_SIZE].copy_from_slice(&index.to_le_bytes());
+        ret[1 + INDEX_SIZE..RECORD_SIZE - CHECKSUM_SIZE].copy_from_slice(chunk.as_ref());
+
+        let checksum = checksum(&ret[..RECORD_SIZE - CHECKSUM_SIZE]);
+        ret[RECORD_SIZE - CHECKSUM_SIZE..].copy_from_slice(&checksum);
+
+        ret
+    }
+
+    /// Returns `None` if the record is corrupted.
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let (body, expected) = bytes.split_at(RECORD_SIZE - CHECKSUM_SIZE);
+
+        if checksum(body) != expected {
+            return None;
+        }
+
+        let mut index = [0; INDEX_SIZE];
+        index.copy_from_slice(&body[1..1 + INDEX_SIZE]);
+        let index = NodeIndex::from_le_bytes(index);
+
+        let mut chunk = Chunk::default();
+        chunk.copy_from_slice(&body[1 + INDEX_SIZE..]);
+
+        match body[0] {
+            0 => Some(Record::Insert(index, chunk)),
+            1 => Some(Record::Remove(index)),
+            2 => Some(Record::Commit(index, chunk)),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the first bytes of the SHA-256 hash of `bytes`.
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let mut ret = [0; CHECKSUM_SIZE];
+    ret.copy_from_slice(&Sha256::digest(bytes)[..CHECKSUM_SIZE]);
+    ret
+}
+
+/// Returns the record which marks a state with `count` nodes and the given `root` as
+/// consistent.
+fn commit_record(count: usize, root: Option<Chunk>) -> Record {
+    Record::Commit(count as NodeIndex, root.unwrap_or_default())
+}
+
+/// An append-only log of the writes made to the nodes of a `Backend`.
+///
+/// Writes are buffered until `commit` is called, which appends them to the file followed by a
+/// commit record holding the number of nodes and the root. When the log is reopened, it is
+/// replayed up to the last commit whose node count and root match the replayed nodes. Anything
+/// after it, such as a truncated or corrupted record, is discarded.
+#[derive(Debug)]
+pub struct NodeLog {
+    file: File,
+    path: PathBuf,
+    // The length of the file up to and including the last commit
+    len: u64,
+    pending: Vec<u8>,
+}
+
+impl NodeLog {
+    /// Opens the log at `path`, creating it if it does not exist, and returns the nodes as of
+    /// the last consistent commit.
+    ///
+    /// Returns an error of kind `InvalidData` if the file is not a log.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<(Self, BTreeMap<NodeIndex, Chunk>)> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let (nodes, len) = if file.metadata()?.len() == 0 {
+            // The log was just created, or never written to
+            file.write_all(MAGIC)?;
+            file.sync_data()?;
+            (BTreeMap::new(), MAGIC.len() as u64)
+        } else {
+            let mut magic = [0; MAGIC.len()];
+
+            match file.read_exact(&mut magic) {
+                Ok(()) if magic == *MAGIC => replay(&mut file)?,
+                Ok(()) => return Err(not_a_log()),
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(not_a_log()),
+                Err(e) => return Err(e),
+            }
+        };
+
+        // Drop anything after the last commit so new records follow it directly
+        file.set_len(len)?;
+
+        let log = Self {
+            file,
+            path,
+            len,
+            pending: vec![],
+        };
+
+        Ok((log, nodes))
+    }
+
+    /// Buffers the write of `chunk` to the node at `index`.
+    pub fn insert(&mut self, index: NodeIndex, chunk: Chunk) {
+        self.pending
+            .extend_from_slice(&Record::Insert(index, chunk).encode());
+    }
+
+    /// Buffers the removal of the node at `index`.
+    pub fn remove(&mut self, index: NodeIndex) {
+        self.pending
+            .extend_from_slice(&Record::Remove(index).encode());
+    }
+
+    /// Appends the buffered writes to the file, marking the state they lead to as consistent.
+    /// That state holds `count` nodes and the given `root`. If an error is returned, the file is
+    /// left as it was at the last commit and the writes remain buffered.
+    pub fn commit(&mut self, count: usize, root: Option<Chunk>) -> io::Result<()> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(&commit_record(count, root).encode());
+
+        let written = self
+            .file
+            .seek(SeekFrom::Start(self.len))
+            .and_then(|_| self.file.write_all(&bytes))
+            .and_then(|_| self.file.sync_data());
+
+        match written {
+            Ok(()) => {
+                self.len += bytes.len() as u64;
+                Ok(())
+            }
+            Err(e) => {
+                // Best effort, since a partial write is discarded when the log is reopened
+                let _ = self.file.set_len(self.len);
+
+                bytes.truncate(bytes.len() - RECORD_SIZE);
+                self.pending = bytes;
+
+                Err(e)
+            }
+        }
+    }
+
+    /// Replaces the log with one that only contains `nodes` and a commit, discarding any
+    /// buffered writes. The new log is written next to the old one and then renamed over it,
+    /// so the old log is kept if an error occurs.
+    pub fn compact<I>(&mut self, nodes: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = io::Result<(NodeIndex, Chunk)>>,
+    {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".compact");
+
+        let mut bytes = MAGIC.to_vec();
+        let mut count = 0;
+        let mut root = None;
+
+        for node in nodes {
+            let (index, chunk) = node?;
+            bytes.extend_from_slice(&Record::Insert(index, chunk).encode());
+            count += 1;
+
+            if index == 0 {
+                root = Some(chunk);
+            }
+        }
+
+        bytes.extend_from_slice(&commit_record(count, root).encode());
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        fs::rename(&tmp, &self.path)?;
+        sync_parent(&self.path)?;
+
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.len = bytes.len() as u64;
+        self.pending.clear();
+
+        Ok(())
+    }
+}
+
+/// The error returned when opening a file which is not a log.
+fn not_a_log() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "file is not a proof log")
+}
+
+/// Replays the records which follow the header of `file`. Returns the nodes as of the last
+/// consistent commit and the length of the file up to that commit.
+///
+/// Only the position of the last commit is kept while reading. If writes after it were replayed,
+/// the nodes are rebuilt once from the records before it.
+fn replay(file: &mut File) -> io::Result<(BTreeMap<NodeIndex, Chunk>, u64)> {
+    let mut reader = BufReader::new(file);
+    let mut nodes = BTreeMap::new();
+    let mut bytes = [0; RECORD_SIZE];
+    // The number of records up to and including the last consistent commit
+    let mut committed = 0;
+    let mut read = 0;
+    let mut uncommitted = false;
+
+    loop {
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => read += 1,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        match Record::decode(&bytes) {
+            Some(commit @ Record::Commit(_, _))
+                if commit == commit_record(nodes.len(), nodes.get(&0).cloned()) =>
+            {
+                committed = read;
+                uncommitted = false;
+            }
+            Some(Record::Commit(_, _)) | None => break,
+            Some(record) => {
+                apply(&mut nodes, record);
+                uncommitted = true;
+            }
+        }
+    }
+
+    if uncommitted {
+        nodes.clear();
+        reader.seek(SeekFrom::Start(MAGIC.len() as u64))?;
+
+        for _ in 0..committed {
+            reader.read_exact(&mut bytes)?;
+
+            if let Some(record) = Record::decode(&bytes) {
+                apply(&mut nodes, record);
+            }
+        }
+    }
+
+    Ok((nodes, MAGIC.len() as u64 + committed * RECORD_SIZE as u64))
+}
+
+/// Applies the write of `record` to `nodes`. Commits do not change the nodes.
+fn apply(nodes: &mut BTreeMap<NodeIndex, Chunk>, record: Record) {
+    match record {
+        Record::Insert(index, chunk) => {
+            nodes.insert(index, chunk);
+        }
+        Record::Remove(index) => {
+            nodes.remove(&index);
+        }
+        Record::Commit(_, _) => (),
+    }
+}
+
+/// Flushes the directory holding `path`, so that a file renamed to `path` keeps its new name
+/// after a crash.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Directories can not be opened as files on other platforms, where renames are left to the
+/// file system.
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_committed_writes() {
+        let path = std::env::temp_dir().join(format!("proof-log-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (mut log, nodes) = NodeLog::open(&path).unwrap();
+        assert!(nodes.is_empty());
+
+        let mut expected = BTreeMap::new();
+        expected.insert(0, Chunk([1; BYTES_PER_CHUNK]));
+        expected.insert(2, Chunk([2; BYTES_PER_CHUNK]));
+
+        log.insert(0, Chunk([1; BYTES_PER_CHUNK]));
+        log.insert(1, Chunk([3; BYTES_PER_CHUNK]));
+        log.insert(2, Chunk([2; BYTES_PER_CHUNK]));
+        log.remove(1);
+        log.commit(expected.len(), expected.get(&0).cloned())
+            .unwrap();
+
+        // Uncommitted writes are discarded
+        log.insert(3, Chunk([3; BYTES_PER_CHUNK]));
+        drop(log);
+
+        let (mut log, nodes) = NodeLog::open(&path).unwrap();
+        assert_eq!(nodes, expected);
+
+        // A truncated record is discarded
+        log.insert(3, Chunk([3; BYTES_PER_CHUNK]));
+        log.commit(3, Some(Chunk([1; BYTES_PER_CHUNK]))).unwrap();
+        drop(log);
+
+        let size = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(size - 1).unwrap();
+
+        let (mut log, nodes) = NodeLog::open(&path).unwrap();
+        assert_eq!(nodes, expected);
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            size - 2 * RECORD_SIZE as u64
+        );
+
+        // A corrupted record is discarded along with every record after it
+        log.insert(3, Chunk([3; BYTES_PER_CHUNK]));
+        log.commit(3, Some(Chunk([1; BYTES_PER_CHUNK]))).unwrap();
+        drop(log);
+
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(size - 2 * RECORD_SIZE as u64 + 20))
+            .unwrap();
+        file.write_all(&[0xff]).unwrap();
+
+        let (mut log, nodes) = NodeLog::open(&path).unwrap();
+        assert_eq!(nodes, expected);
+
+        // Removed and overwritten nodes are restored if their commit is lost
+        log.remove(2);
+        log.insert(0, Chunk([9; BYTES_PER_CHUNK]));
+        log.commit(1, Some(Chunk([9; BYTES_PER_CHUNK]))).unwrap();
+        drop(log);
+
+        let size = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(size - 1).unwrap();
+
+        let (mut log, nodes) = NodeLog::open(&path).unwrap();
+        assert_eq!(nodes, expected);
+
+        // Compacting keeps the nodes but drops the history
+        log.compact(expected.clone().into_iter().map(Ok)).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            (MAGIC.len() + 3 * RECORD_SIZE) as u64
+        );
+        drop(log);
+
+        let (_, nodes) = NodeLog::open(&path).unwrap();
+        assert_eq!(nodes, expected);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reject_other_files() {
+        let path = std::env::temp_dir().join(format!("proof-not-log-{}", std::process::id()));
+
+        // Files shorter than the header are left untouched too
+        for contents in &[&b"definitely not a log"[..], b"short"] {
+            fs::write(&path, contents).unwrap();
+
+            assert_eq!(
+                NodeLog::open(&path).map(|_| ()).map_err(|e| e.kind()),
+                Err(io::ErrorKind::InvalidData)
+            );
+            assert_eq!(fs::read(&path).unwrap(), *contents);
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+}
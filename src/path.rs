@@ -1,4 +1,5 @@
-use crate::NodeIndex;
+use crate::error::Error;
+use std::str::FromStr;
 
 /// An identifier for the location of a distinct value in a partial.
 #[derive(Clone, Debug, PartialEq)]
@@ -6,7 +7,7 @@ pub enum PathElement {
     /// An identifier for a member of a container object or for the length of a list.
     Ident(String),
     /// An identifier for the position of a value in a homogeneous collection.
-    Index(NodeIndex),
+    Index(u64),
 }
 
 impl PathElement {
@@ -37,3 +38,153 @@ impl From<&str> for PathElement {
         }
     }
 }
+
+/// A sequence of `PathElement`s identifying a value in a partial.
+///
+/// Paths can be parsed from strings where members are separated by `.` or `/` and positions are
+/// either their own member or wrapped in brackets, e.g. `messages[3].timestamp` or `b/2/len`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path(Vec<PathElement>);
+
+impl Path {
+    /// Returns the elements of the path.
+    pub fn elements(&self) -> &[PathElement] {
+        &self.0
+    }
+}
+
+impl From<Vec<PathElement>> for Path {
+    fn from(elements: Vec<PathElement>) -> Path {
+        Path(elements)
+    }
+}
+
+impl From<Path> for Vec<PathElement> {
+    fn from(path: Path) -> Vec<PathElement> {
+        path.0
+    }
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, element) in self.0.iter().enumerate() {
+            match element {
+                PathElement::Ident(_) if i > 0 => write!(fmt, ".{}", element),
+                PathElement::Ident(_) => write!(fmt, "{}", element),
+                PathElement::Index(_) => write!(fmt, "[{}]", element),
+            }?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Path, Error> {
+        let mut elements = vec![];
+
+        if s.is_empty() {
+            return Ok(Path(elements));
+        }
+
+        for member in s.split(['.', '/']) {
+            let malformed = || Error::MalformedPath(s.to_string());
+
+            // Split `name[1][2]` into `name` and `[1][2]`.
+            let (name, mut indices) = member.split_at(member.find('[').unwrap_or(member.len()));
+
+            if name.contains(']') || (name.is_empty() && indices.is_empty()) {
+                return Err(malformed());
+            }
+
+            if !name.is_empty() {
+                elements.push(PathElement::from(name));
+            }
+
+            while !indices.is_empty() {
+                let end = indices.find(']').ok_or_else(malformed)?;
+                let index = indices[1..end].parse().map_err(|_| malformed())?;
+
+                elements.push(PathElement::Index(index));
+                indices = &indices[end + 1..];
+
+                if !indices.is_empty() && !indices.starts_with('[') {
+                    return Err(malformed());
+                }
+            }
+        }
+
+        Ok(Path(elements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::hash_children;
+    use crate::chunk::Chunk;
+    use crate::merkle_tree_overlay::MerkleTreeOverlay;
+    use crate::ser::SerializedProof;
+    use crate::test_utils::{b, S};
+    use crate::Proof;
+
+    #[test]
+    fn parse_path() {
+        let expected = Path(vec![
+            PathElement::from_ident_str("messages"),
+            PathElement::Index(3),
+            PathElement::from_ident_str("timestamp"),
+        ]);
+
+        assert_eq!("messages[3].timestamp".parse(), Ok(expected.clone()));
+        assert_eq!("messages/3/timestamp".parse(), Ok(expected.clone()));
+        assert_eq!("messages.3.timestamp".parse(), Ok(expected.clone()));
+
+        assert_eq!(
+            "[0][1].len".parse(),
+            Ok(Path(vec![
+                PathElement::Index(0),
+                PathElement::Index(1),
+                PathElement::from_ident_str("len"),
+            ]))
+        );
+
+        assert_eq!("".parse(), Ok(Path(vec![])));
+
+        for s in &["a..b", "a.", "a[", "a[x]", "a[1]b", "a]", "a[1]]", "a[]"] {
+            assert_eq!(s.parse::<Path>(), Err(Error::MalformedPath(s.to_string())));
+        }
+    }
+
+    #[test]
+    fn display_path() {
+        for s in &["messages[3].timestamp", "b[2].len", "[0][1]", "a"] {
+            assert_eq!(s.parse::<Path>().unwrap().to_string(), *s);
+        }
+
+        assert_eq!("b/2/len".parse::<Path>().unwrap().to_string(), "b[2].len");
+    }
+
+    #[test]
+    fn string_paths() {
+        let path = |s: &str| s.parse::<Path>().unwrap();
+
+        assert_eq!(S::get_node(path("b[2]").into()), S::get_node(b(2)));
+
+        let serialized_proof = SerializedProof::from_nodes(vec![
+            (23, Chunk([1; 32])),
+            (24, Chunk([2; 32])),
+            (12, hash_children(&Chunk::default(), &Chunk::default())),
+            (6, Chunk::default()),
+            (1, Chunk::default()),
+        ]);
+        let mut proof = Proof::<S>::new(serialized_proof.clone());
+
+        assert_eq!(proof.fill(), Ok(()));
+        assert_eq!(proof.extract(path("b/2")), Ok(serialized_proof));
+        assert_eq!(proof.get_bytes(path("b[3]")), Ok(vec![2; 16]));
+        assert_eq!(proof.get_bytes(path("b.len")), Ok(vec![0; 32]));
+    }
+}
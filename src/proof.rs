@@ -1,130 +1,568 @@
-use super::{NodeIndex, SerializedProof, BYTES_PER_CHUNK};
+use super::{CompactProof, NodeIndex, Patch, SerializedProof, BYTES_PER_CHUNK};
 use crate::backend::Backend;
+use crate::chunk::Chunk;
 use crate::error::{Error, Result};
-use crate::merkle_tree_overlay::MerkleTreeOverlay;
+use crate::hasher::{Hasher, Sha256Hasher};
+use crate::merkle_tree_overlay::{MerkleTreeOverlay, MerkleTreeValue};
 use crate::path::PathElement;
-use crate::tree_arithmetic::zeroed::sibling_index;
+use crate::ser::check_leaves;
+use crate::store::NodeStore;
+use crate::tree_arithmetic::zeroed::{
+    expand_tree_index, get_branch_indices, get_helper_indices, get_path_indices, index_depth,
+};
+use ssz::{Decode, Encode};
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io;
 use std::marker::PhantomData;
+use std::path::Path;
 
 /// A `Proof` is generated from a `SerializedProof` and can manipulate / verify data in the
-/// merkle tree.
-#[derive(Clone, Debug, Default, PartialEq)]
-pub struct Proof<T: MerkleTreeOverlay> {
-    db: Backend,
+/// merkle tree. Nodes are hashed with `H`, which defaults to SHA-256, and kept in `S`, which
+/// defaults to a `HashMap`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Proof<
+    T: MerkleTreeOverlay,
+    H: Hasher = Sha256Hasher,
+    S: NodeStore = HashMap<NodeIndex, Chunk>,
+> {
+    db: Backend<H, S>,
+    strict_lengths: bool,
     _phantom: PhantomData<T>,
 }
 
-impl<T: MerkleTreeOverlay> Proof<T> {
+impl<T: MerkleTreeOverlay, H: Hasher, S: NodeStore> Proof<T, H, S> {
     /// Initialize `Proof` directly from a `SerializedProof`.
-    pub fn new(proof: SerializedProof) -> Self {
-        let mut ret = Self {
-            db: Backend::new(),
+    ///
+    /// # Panics
+    /// Panics if `proof` is invalid. Use `Proof::try_from` for proofs from untrusted sources.
+    pub fn new(proof: SerializedProof) -> Self
+    where
+        S: Default,
+    {
+        Self::try_from(proof).expect("serialized proof to be valid")
+    }
+
+    /// Initialize `Proof` by merkleizing every node of `value`.
+    ///
+    /// # Panics
+    /// Panics if a node of `value` does not fit in a `NodeIndex`. Use `Proof::try_from_value` for
+    /// deeply nested types.
+    pub fn from_value(value: &T) -> Self
+    where
+        T: MerkleTreeValue,
+        S: Default,
+    {
+        Self::try_from_value(value).expect("value to fit in the tree")
+    }
+
+    /// Initialize `Proof` by merkleizing every node of `value`, returning
+    /// `Error::IndexOverflow` if a node does not fit in a `NodeIndex`.
+    pub fn try_from_value(value: &T) -> Result<Self>
+    where
+        T: MerkleTreeValue,
+        S: Default,
+    {
+        let mut ret = Self::default();
+        value.merkleize(&mut ret.db, 0)?;
+        Ok(ret)
+    }
+
+    /// Initialize `Proof` with the nodes kept in `store`, including any nodes that are already
+    /// stored.
+    pub fn with_store(store: S) -> Self {
+        Self {
+            db: Backend::with_store(store, T::zero_subtree_height),
+            strict_lengths: false,
             _phantom: PhantomData,
-        };
+        }
+    }
 
-        // This will always return `Ok(())` since the `db` is starting empty.
-        ret.load(proof).unwrap();
+    /// Opens the `Proof` persisted in the log at `path`, creating an empty log if the file does
+    /// not exist. Every later write to the proof is appended to the log once it is committed.
+    ///
+    /// The nodes are restored as of the last successful `commit`, so a truncated or corrupted log
+    /// falls back to the last consistent root.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self>
+    where
+        S: Default,
+    {
+        Ok(Self {
+            db: Backend::open(path, T::zero_subtree_height)?,
+            strict_lengths: false,
+            _phantom: PhantomData,
+        })
+    }
 
-        ret
+    /// Durably appends the writes made since the last commit to the log of a `Proof` opened with
+    /// `open`, so that they are restored when it is reopened. Does nothing for other proofs.
+    pub fn commit(&mut self) -> io::Result<()> {
+        self.db.commit()
+    }
+
+    /// Rewrites the log of a `Proof` opened with `open` so that it only contains the current
+    /// nodes, committing them. Does nothing for other proofs.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.db.compact()
+    }
+
+    /// Enables or disables strict lengths. When enabled, `extract`, `get_bytes` and `set_bytes`
+    /// return `Error::IndexBeyondLength` for positions at or beyond the proven length of a list.
+    pub fn set_strict_lengths(&mut self, strict: bool) {
+        self.strict_lengths = strict;
     }
 
     /// Populate the struct's cache with a `SerializedProof`.
+    ///
+    /// The proof is validated before any node is inserted, so the cache is left unchanged if it
+    /// is invalid. Nodes which can not exist in the tree of `T`, such as the children of a
+    /// chunk holding a basic value, are rejected with `Error::InvalidIndex`.
     pub fn load(&mut self, proof: SerializedProof) -> Result<()> {
-        for (i, index) in proof.indices.iter().enumerate() {
-            let chunk = proof.chunks[i * BYTES_PER_CHUNK..(i + 1) * BYTES_PER_CHUNK].to_vec();
-            self.db.insert(*index, chunk.clone());
+        let expected = proof.indices.len() * BYTES_PER_CHUNK;
+
+        if proof.chunks.len() != expected {
+            return Err(Error::ChunkLengthMismatch(expected, proof.chunks.len()));
+        }
+
+        let mut indices = proof.indices.clone();
+        indices.sort();
+
+        if let Some(w) = indices.windows(2).find(|w| w[0] == w[1]) {
+            return Err(Error::DuplicateIndex(w[0]));
+        }
+
+        if let Some(index) = indices.iter().find(|i| index_depth(**i) > T::max_depth()) {
+            return Err(Error::InvalidIndex(*index));
+        }
+
+        for index in &indices {
+            check_reachable::<T>(*index)?;
+        }
+
+        for (index, chunk) in proof
+            .indices
+            .iter()
+            .zip(proof.chunks.chunks(BYTES_PER_CHUNK))
+        {
+            self.db.insert(*index, Chunk::try_from(chunk)?)?;
         }
 
         Ok(())
     }
 
-    /// Generates a `SerializedProof` proving that `path` is a part of the current merkle tree.
-    pub fn extract(&self, path: Vec<PathElement>) -> Result<SerializedProof> {
-        if path.is_empty() {
-            return Err(Error::EmptyPath());
+    /// Returns a `SerializedProof` containing every loaded node, in increasing order of index.
+    pub fn to_serialized(&self) -> Result<SerializedProof> {
+        let mut indices = self.db.nodes()?;
+        indices.sort();
+
+        let mut nodes = vec![];
+        for index in indices {
+            if let Some(chunk) = self.db.get(index)? {
+                nodes.push((index, chunk));
+            }
         }
 
-        let node = T::get_node(path)?;
+        Ok(SerializedProof::from_nodes(nodes))
+    }
+
+    /// Combines the nodes of `other` into the current proof. Both proofs must agree on the chunk
+    /// of every node they have in common, otherwise `Error::ChunkConflict` is returned and the
+    /// current proof is left unchanged.
+    pub fn merge(&mut self, other: Proof<T, H, S>) -> Result<()> {
+        self.db.merge(other.db)
+    }
 
-        let mut visitor = node.index;
-        let mut indices: Vec<NodeIndex> = vec![visitor];
-        let mut chunks: Vec<u8> = self
+    /// Returns every node whose chunk differs from `other`, including the nodes which are only
+    /// loaded in one of the proofs. Each node is mapped to the path of the value it holds, if
+    /// `T::get_path` can find one.
+    pub fn diff<O: NodeStore>(
+        &self,
+        other: &Proof<T, H, O>,
+    ) -> Result<BTreeMap<NodeIndex, Option<Vec<PathElement>>>> {
+        Ok(self
             .db
-            .get(visitor)
-            .ok_or(Error::ChunkNotLoaded(visitor))?
-            .clone();
-
-        while visitor > 0 {
-            let sibling = sibling_index(visitor);
-            let left = 2 * sibling + 1;
-            let right = 2 * sibling + 2;
-
-            if !(indices.contains(&left) && indices.contains(&right)) {
-                indices.push(sibling);
-                chunks.extend(self.db.get(sibling).ok_or(Error::ChunkNotLoaded(sibling))?);
+            .diff(&other.db)?
+            .into_iter()
+            .map(|index| (index, T::get_path(index)))
+            .collect())
+    }
+
+    /// Returns the `Patch` which moves the current proof to the nodes and root of `newer`.
+    ///
+    /// Returns `Error::RootNotLoaded` if `newer` has no root.
+    pub fn patch<O: NodeStore>(&self, newer: &Proof<T, H, O>) -> Result<Patch> {
+        let root = newer.root()?;
+        let mut nodes = vec![];
+        let mut removed = vec![];
+
+        for index in self.db.diff(&newer.db)? {
+            match newer.db.get(index)? {
+                Some(chunk) => nodes.push((index, chunk)),
+                None => removed.push(index),
+            }
+        }
+
+        Ok(Patch {
+            root,
+            nodes: SerializedProof::from_nodes(nodes),
+            removed,
+        })
+    }
+
+    /// Applies `patch` to move the current proof to `patch.root`. If the patched tree is not
+    /// valid for `patch.root`, the verification error is returned and the current proof is left
+    /// unchanged.
+    pub fn apply(&mut self, patch: &Patch) -> Result<()> {
+        let expected = patch.nodes.indices.len() * BYTES_PER_CHUNK;
+
+        if patch.nodes.chunks.len() != expected {
+            return Err(Error::ChunkLengthMismatch(
+                expected,
+                patch.nodes.chunks.len(),
+            ));
+        }
+
+        // The chunks replaced by the patch, which are restored if it is invalid
+        let mut old = BTreeMap::new();
+
+        let mut write = || -> Result<()> {
+            for (index, chunk) in patch.nodes.nodes() {
+                let chunk = self.db.insert(index, chunk)?;
+                old.entry(index).or_insert(chunk);
+            }
+
+            for index in &patch.removed {
+                let chunk = self.db.remove(*index)?;
+                old.entry(*index).or_insert(chunk);
+            }
+
+            self.db.verify(&patch.root)
+        };
+
+        let verified = write();
+
+        if verified.is_err() {
+            for (index, chunk) in old {
+                match chunk {
+                    Some(chunk) => self.db.insert(index, chunk)?,
+                    None => self.db.remove(index)?,
+                };
             }
+        }
 
-            // visitor /= 2, when 1 indexed
-            visitor = (visitor + 1) / 2 - 1;
+        verified
+    }
+
+    /// Generates a `SerializedProof` proving that `path` is a part of the current merkle tree.
+    pub fn extract(&self, path: impl Into<Vec<PathElement>>) -> Result<SerializedProof> {
+        self.extract_many(&[path.into()])
+    }
+
+    /// Generates a single `SerializedProof` proving that each of `paths` is a part of the current
+    /// merkle tree.
+    ///
+    /// The leaves are listed first, in the order of `paths`, followed by the helper nodes in
+    /// decreasing order. Helpers shared between the paths are only included once.
+    pub fn extract_many<P>(&self, paths: &[P]) -> Result<SerializedProof>
+    where
+        P: Clone + Into<Vec<PathElement>>,
+    {
+        let proof = self.extract_compact(paths)?;
+
+        Ok(SerializedProof {
+            indices: proof.indices()?,
+            chunks: proof.chunks,
+        })
+    }
+
+    /// Generates a `CompactProof` proving that each of `paths` is a part of the current merkle
+    /// tree. Returns `Error::InvalidIndex` if the node of one path is an ancestor of another's.
+    pub fn extract_compact<P>(&self, paths: &[P]) -> Result<CompactProof>
+    where
+        P: Clone + Into<Vec<PathElement>>,
+    {
+        for path in paths {
+            self.check_lengths(&path.clone().into())?;
+        }
+
+        let leaves = leaf_indices_helper::<T, P>(paths)?;
+        check_leaves(&leaves)?;
+
+        let mut chunks: Vec<u8> = vec![];
+
+        for index in leaves.iter().chain(get_helper_indices(&leaves).iter()) {
+            chunks.extend_from_slice(
+                self.db
+                    .get(*index)?
+                    .ok_or(Error::ChunkNotLoaded(*index))?
+                    .as_ref(),
+            );
         }
 
-        Ok(SerializedProof { indices, chunks })
+        Ok(CompactProof { leaves, chunks })
+    }
+
+    /// Removes every node which is not needed to prove `paths`. The leaves of `paths`, their
+    /// helper nodes and their ancestors are kept.
+    pub fn prune<P>(&mut self, paths: &[P]) -> Result<()>
+    where
+        P: Clone + Into<Vec<PathElement>>,
+    {
+        let leaves = leaf_indices_helper::<T, P>(paths)?;
+
+        let mut keep = get_helper_indices(&leaves);
+        for leaf in &leaves {
+            keep.extend(get_path_indices(*leaf));
+        }
+        keep.extend(leaves);
+        keep.push(0);
+
+        keep.sort();
+        keep.dedup();
+
+        self.db.retain(|index| keep.binary_search(&index).is_ok())
     }
 
     /// Returns the bytes representation of the object associated with `path`
-    pub fn get_bytes(&self, path: Vec<PathElement>) -> Result<Vec<u8>> {
+    pub fn get_bytes(&self, path: impl Into<Vec<PathElement>>) -> Result<Vec<u8>> {
+        let path = path.into();
+
         if path.is_empty() {
             return Err(Error::EmptyPath());
         }
 
+        self.check_lengths(&path)?;
         let (index, begin, end) = bytes_at_path_helper::<T>(path)?;
 
-        Ok(self.db.get(index).ok_or(Error::ChunkNotLoaded(index))?[begin..end].to_vec())
+        Ok(self.db.get(index)?.ok_or(Error::ChunkNotLoaded(index))?[begin..end].to_vec())
     }
 
-    /// Replaces the bytes at `path` with `bytes`.
-    pub fn set_bytes(&mut self, path: Vec<PathElement>, bytes: Vec<u8>) -> Result<()> {
-        if path.is_empty() {
-            return Err(Error::EmptyPath());
+    /// Returns the value associated with `path`, decoded from its SSZ representation.
+    pub fn get<V: Decode>(&self, path: impl Into<Vec<PathElement>>) -> Result<V> {
+        let path = path.into();
+        check_value_size::<T>(&path, V::is_ssz_fixed_len(), V::ssz_fixed_len())?;
+        V::from_ssz_bytes(&self.get_bytes(path)?).map_err(Error::Decode)
+    }
+
+    /// Replaces the value at `path` with the SSZ representation of `value`.
+    pub fn set<V: Encode>(&mut self, path: impl Into<Vec<PathElement>>, value: &V) -> Result<()> {
+        let path = path.into();
+        check_value_size::<T>(&path, V::is_ssz_fixed_len(), V::ssz_fixed_len())?;
+        self.set_bytes(path, value.as_ssz_bytes())
+    }
+
+    /// Replaces the bytes at `path` with `bytes` and recalculates the affected ancestors.
+    ///
+    /// Returns `Error::SizeMismatch` unless `bytes` is exactly the size of the value at `path`.
+    pub fn set_bytes(&mut self, path: impl Into<Vec<PathElement>>, bytes: Vec<u8>) -> Result<()> {
+        self.set_bytes_many(vec![(path, bytes)])
+    }
+
+    /// Replaces the bytes at each path with the associated bytes, recalculating the ancestors
+    /// shared between the paths only once.
+    ///
+    /// Every update is checked before any bytes are written, so the proof is left unchanged if
+    /// an error is returned. Lengths are checked against the proof as it was before the call.
+    pub fn set_bytes_many<P: Into<Vec<PathElement>>>(
+        &mut self,
+        updates: Vec<(P, Vec<u8>)>,
+    ) -> Result<()> {
+        let updates = updates
+            .into_iter()
+            .map(|(path, bytes)| {
+                let path = path.into();
+                self.check_lengths(&path)?;
+                Ok((self.locate_bytes(path, bytes.len())?, bytes))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for ((index, begin, _), bytes) in updates {
+            self.write_chunk(index, begin, &bytes)?;
         }
 
+        self.db.rehash()
+    }
+
+    /// Replaces the bytes at `path` with `bytes` without recalculating any ancestors.
+    fn write_bytes(&mut self, path: Vec<PathElement>, bytes: Vec<u8>) -> Result<()> {
+        let (index, begin, _) = self.locate_bytes(path, bytes.len())?;
+        self.write_chunk(index, begin, &bytes)
+    }
+
+    /// Returns the index of the node holding the value at `path` and the range of the value
+    /// within its chunk. The chunk must be loaded and `len` must be the exact size of the value.
+    fn locate_bytes(
+        &self,
+        path: Vec<PathElement>,
+        len: usize,
+    ) -> Result<(NodeIndex, usize, usize)> {
         let (index, begin, end) = bytes_at_path_helper::<T>(path)?;
 
-        let chunk = self
-            .db
-            .get(index)
-            .ok_or(Error::ChunkNotLoaded(index))?
-            .to_vec()
-            .iter()
-            .cloned()
-            .enumerate()
-            .map(|(i, b)| {
-                if i >= begin && i < end {
-                    bytes[i - begin]
+        if !self.db.contains_node(index)? {
+            return Err(Error::ChunkNotLoaded(index));
+        }
+
+        if len != end - begin {
+            return Err(Error::SizeMismatch(end - begin, len));
+        }
+
+        Ok((index, begin, end))
+    }
+
+    /// Writes `bytes` into the loaded chunk at `index`, starting at `begin`.
+    fn write_chunk(&mut self, index: NodeIndex, begin: usize, bytes: &[u8]) -> Result<()> {
+        let mut chunk = self.db.get(index)?.ok_or(Error::ChunkNotLoaded(index))?;
+        chunk[begin..begin + bytes.len()].copy_from_slice(bytes);
+
+        self.db.update(index, chunk)?;
+        Ok(())
+    }
+
+    /// Appends the value represented by `bytes` to the list at `path`, updating the list's length
+    /// and recalculating the affected ancestors.
+    ///
+    /// The chunk which will hold the value, the length, and the siblings of both branches must be
+    /// loaded. Returns `Error::IndexOutOfBounds` if the list is already at its limit.
+    pub fn push(&mut self, path: impl Into<Vec<PathElement>>, bytes: Vec<u8>) -> Result<()> {
+        let path = path.into();
+        let len = self.len(path.clone())?;
+
+        let mut element = path.clone();
+        element.push(PathElement::Index(len));
+
+        let size = T::get_node(element.clone())?.size as usize;
+        if bytes.len() != size {
+            return Err(Error::SizeMismatch(size, bytes.len()));
+        }
+
+        self.write_list(path, element, bytes, len + 1)
+    }
+
+    /// Removes the last value of the list at `path` and returns its bytes, updating the list's
+    /// length and recalculating the affected ancestors. The removed value is replaced with zeros.
+    ///
+    /// The chunk holding the value, the length, and the siblings of both branches must be loaded.
+    pub fn pop(&mut self, path: impl Into<Vec<PathElement>>) -> Result<Vec<u8>> {
+        let path = path.into();
+        let len = self.len(path.clone())?;
+
+        if len == 0 {
+            return Err(Error::EmptyList());
+        }
+
+        let mut element = path.clone();
+        element.push(PathElement::Index(len - 1));
+
+        let (index, begin, end) = bytes_at_path_helper::<T>(element.clone())?;
+        let bytes = self.db.get(index)?.ok_or(Error::ChunkNotLoaded(index))?[begin..end].to_vec();
+
+        self.write_list(path, element, vec![0; end - begin], len - 1)?;
+        Ok(bytes)
+    }
+
+    /// Replaces the bytes at `element` of the list at `path` and sets the list's length to `len`.
+    /// Nothing is written unless the ancestors of both nodes can be recalculated.
+    fn write_list(
+        &mut self,
+        path: Vec<PathElement>,
+        element: Vec<PathElement>,
+        bytes: Vec<u8>,
+        len: u64,
+    ) -> Result<()> {
+        let mut len_path = path;
+        len_path.push(PathElement::from_ident_str("len"));
+
+        let (element_index, _, _) = bytes_at_path_helper::<T>(element.clone())?;
+        let (len_index, _, _) = bytes_at_path_helper::<T>(len_path.clone())?;
+
+        for index in &[element_index, len_index] {
+            if !self.db.contains_node(*index)? {
+                return Err(Error::ChunkNotLoaded(*index));
+            }
+
+            for sibling in get_branch_indices(*index) {
+                if !self.db.contains_node(sibling)? {
+                    return Err(Error::SiblingNotLoaded(sibling));
+                }
+            }
+        }
+
+        self.write_bytes(element, bytes)?;
+        self.write_bytes(len_path, Chunk::padded(&len.to_le_bytes()).to_vec())?;
+        self.db.rehash()
+    }
+
+    /// Returns the length of the list at `path`, decoded from its length mix-in. An empty `path`
+    /// refers to the proof's root.
+    pub fn len(&self, path: impl Into<Vec<PathElement>>) -> Result<u64> {
+        let mut path = path.into();
+        path.push(PathElement::from_ident_str("len"));
+
+        let (index, begin, _) = bytes_at_path_helper::<T>(path)?;
+        let chunk = self.db.get(index)?.ok_or(Error::ChunkNotLoaded(index))?;
+
+        let mut len = [0; 8];
+        len.copy_from_slice(&chunk[begin..begin + 8]);
+
+        Ok(u64::from_le_bytes(len))
+    }
+
+    /// Ensures that each position in `path` is less than the proven length of its list, if strict
+    /// lengths are enabled.
+    fn check_lengths(&self, path: &[PathElement]) -> Result<()> {
+        if !self.strict_lengths {
+            return Ok(());
+        }
+
+        for (i, element) in path.iter().enumerate() {
+            if let PathElement::Index(position) = element {
+                let parent = &path[..i];
+
+                let is_list = if parent.is_empty() {
+                    T::is_list()
                 } else {
-                    b
+                    T::get_node(parent.to_vec())?.is_list
+                };
+
+                if is_list {
+                    let len = self.len(parent.to_vec())?;
+
+                    if *position >= len {
+                        return Err(Error::IndexBeyondLength(*position, len));
+                    }
                 }
-            })
-            .collect();
+            }
+        }
 
-        self.db.insert(index, chunk);
         Ok(())
     }
 
     /// Determines if the current merkle tree is valid.
-    pub fn is_valid(&self, root: Vec<u8>) -> bool {
+    pub fn is_valid(&self, root: Chunk) -> bool {
         self.db.is_valid(root)
     }
 
+    /// Verifies the current merkle tree against `root`, returning an error describing the first
+    /// inconsistency found.
+    pub fn verify(&self, root: &Chunk) -> Result<()> {
+        self.db.verify(root)
+    }
+
     /// Inserts missing nodes into the merkle tree that can be generated from existing nodes.
+    ///
+    /// Padding omitted from the proof is inserted as the root of an all-zero subtree, as
+    /// described by `MerkleTreeOverlay::zero_subtree_height`.
     pub fn fill(&mut self) -> Result<()> {
         self.db.fill()
     }
 
-    /// Returns the root node of the proof if it has been calculated.
-    pub fn root(&self) -> Option<&Vec<u8>> {
-        self.db.get(0)
+    /// Returns the root node of the proof if it has been calculated, otherwise
+    /// `Error::RootNotLoaded`.
+    ///
+    /// Writes made through `set_bytes` keep the root up to date.
+    pub fn root(&self) -> Result<Chunk> {
+        self.db.get(0)?.ok_or(Error::RootNotLoaded())
     }
 
     /// Recalculates all intermediate nodes and root using the available leaves.
@@ -133,6 +571,26 @@ impl<T: MerkleTreeOverlay> Proof<T> {
     }
 }
 
+impl<T: MerkleTreeOverlay, H: Hasher, S: NodeStore + Default> Default for Proof<T, H, S> {
+    fn default() -> Self {
+        Self::with_store(S::default())
+    }
+}
+
+impl<T: MerkleTreeOverlay, H: Hasher, S: NodeStore + Default> TryFrom<SerializedProof>
+    for Proof<T, H, S>
+{
+    type Error = Error;
+
+    fn try_from(proof: SerializedProof) -> Result<Self> {
+        let mut ret = Self::default();
+
+        ret.load(proof)?;
+
+        Ok(ret)
+    }
+}
+
 /// Recursively traverse the tree structure, matching the appropriate `path` element with its index,
 /// eventually returning the chunk index, beginning offset, and end offset of the associated value.
 fn bytes_at_path_helper<T: MerkleTreeOverlay + ?Sized>(
@@ -150,3 +608,509 @@ fn bytes_at_path_helper<T: MerkleTreeOverlay + ?Sized>(
         (node.offset + node.size).into(),
     ))
 }
+
+/// Returns the unique node indices of the values associated with `paths`, in the order of `paths`.
+fn leaf_indices_helper<T, P>(paths: &[P]) -> Result<Vec<NodeIndex>>
+where
+    T: MerkleTreeOverlay + ?Sized,
+    P: Clone + Into<Vec<PathElement>>,
+{
+    let mut leaves: Vec<NodeIndex> = vec![];
+    let mut seen: HashSet<NodeIndex> = HashSet::new();
+
+    for path in paths {
+        let path: Vec<PathElement> = path.clone().into();
+
+        if path.is_empty() {
+            return Err(Error::EmptyPath());
+        }
+
+        let index = T::get_node(path)?.index;
+
+        if seen.insert(index) {
+            leaves.push(index);
+        }
+    }
+
+    Ok(leaves)
+}
+
+/// Ensures that a value with the SSZ fixed length `len` occupies exactly the node at `path`.
+/// Returns `Error::InvalidIndex` if the node at `index` can not exist in the tree of `T` because
+/// it is below a node without children, such as the chunk of a basic value, a list's length or
+/// the padding of a container.
+///
+/// The nearest node at or above `index` which `T::get_path` maps to a value bounds how deep the
+/// nodes below it can be. Types which do not implement `get_path` are not checked.
+fn check_reachable<T: MerkleTreeOverlay + ?Sized>(index: NodeIndex) -> Result<()> {
+    let mut ancestor = index;
+
+    loop {
+        if let Some(path) = T::get_path(ancestor) {
+            if ancestor == index {
+                return Ok(());
+            }
+
+            let height = if path.is_empty() {
+                T::height()
+            } else {
+                T::get_node(path)
+                    .map_err(|_| Error::InvalidIndex(index))?
+                    .height
+            };
+
+            return if index_depth(index) - index_depth(ancestor) > height {
+                Err(Error::InvalidIndex(index))
+            } else {
+                Ok(())
+            };
+        }
+
+        if ancestor == 0 {
+            return Ok(());
+        }
+
+        ancestor = expand_tree_index(ancestor).2;
+    }
+}
+
+fn check_value_size<T: MerkleTreeOverlay + ?Sized>(
+    path: &[PathElement],
+    is_fixed_len: bool,
+    len: usize,
+) -> Result<()> {
+    if path.is_empty() {
+        return Err(Error::EmptyPath());
+    }
+
+    let size = T::get_node(path.to_vec())?.size as usize;
+
+    if is_fixed_len && len == size {
+        Ok(())
+    } else {
+        Err(Error::SizeMismatch(size, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::hash_children;
+    use crate::test_utils::{b, s, s_proof, S};
+    use crate::types::VariableList;
+    use ethereum_types::U256;
+    use typenum::U8;
+
+    /// Returns the filled proof of `S` with `b = [1, 2, 3, 4, 5, 6]` in its first six chunks.
+    fn six_leaves() -> (Proof<S>, [u8; 192]) {
+        let mut arr = [0_u8; 192];
+
+        for i in 0..6 {
+            arr[32 * i + 31] = i as u8 + 1;
+        }
+
+        let mut p = Proof::<S>::new(SerializedProof {
+            indices: vec![1, 23, 24, 25, 26, 6],
+            chunks: arr.to_vec(),
+        });
+        assert_eq!(p.fill(), Ok(()));
+
+        (p, arr)
+    }
+
+    #[test]
+    fn extract_multiple_paths() {
+        let (p, arr) = six_leaves();
+        let chunk = |i: usize| arr[i * 32..(i + 1) * 32].to_vec();
+
+        // Shared helpers are only included once and duplicate leaves are dropped
+        assert_eq!(
+            p.extract_many(&[b(0), b(1), b(4)]),
+            Ok(SerializedProof {
+                indices: vec![23, 25, 26, 24, 6, 1],
+                chunks: vec![chunk(1), chunk(3), chunk(4), chunk(2), chunk(5), chunk(0)]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            })
+        );
+
+        // A single path is equivalent to `extract`
+        assert_eq!(p.extract_many(&[b(2)]), p.extract(b(2)));
+
+        // Check for `Error::EmptyPath()`
+        assert_eq!(
+            p.extract_many(&[vec![PathElement::from_ident_str("a")], vec![]]),
+            Err(Error::EmptyPath())
+        );
+    }
+
+    #[test]
+    fn set_bytes_updates_root() {
+        let (mut p, mut arr) = six_leaves();
+
+        assert_eq!(p.set_bytes(b(0), vec![9; 16]), Ok(()));
+        assert_eq!(
+            p.set_bytes(b(0), vec![9; 17]),
+            Err(Error::SizeMismatch(16, 17))
+        );
+        assert_eq!(
+            p.set_bytes_many(vec![(b(3), vec![8; 16]), (b(6), vec![7; 16])]),
+            Ok(())
+        );
+
+        // Build the expected tree from scratch
+        arr[32..48].copy_from_slice(&[9; 16]);
+        arr[80..96].copy_from_slice(&[8; 16]);
+        arr[128..144].copy_from_slice(&[7; 16]);
+
+        let mut expected = Proof::<S>::new(SerializedProof {
+            indices: vec![1, 23, 24, 25, 26, 6],
+            chunks: arr.to_vec(),
+        });
+        assert_eq!(expected.fill(), Ok(()));
+
+        assert_eq!(p.root(), expected.root());
+        assert!(p.is_valid(expected.root().unwrap()));
+
+        // Nothing is written if any of the updates is invalid
+        assert_eq!(
+            p.set_bytes_many(vec![(b(1), vec![6; 16]), (b(2), vec![6; 8])]),
+            Err(Error::SizeMismatch(16, 8))
+        );
+        assert_eq!(
+            p.set_bytes_many(vec![(b(1), vec![6; 16]), (b(2), vec![6; 17])]),
+            Err(Error::SizeMismatch(16, 17))
+        );
+        assert_eq!(
+            p.set_bytes_many(vec![
+                (b(1), vec![6; 16]),
+                (vec![PathElement::from_ident_str("c")], vec![6; 32])
+            ]),
+            Err(Error::InvalidPath(PathElement::from_ident_str("c")))
+        );
+        assert_eq!(p.root(), expected.root());
+        assert_eq!(p.get_bytes(b(1)), expected.get_bytes(b(1)));
+    }
+
+    #[test]
+    fn reject_malicious_indices() {
+        let chunk = |i: NodeIndex| (i, Chunk::default());
+
+        // Nodes below the deepest node of `S` can not be loaded, nor filled
+        for index in &[NodeIndex::MAX, 31, 1 << 100] {
+            let proof = SerializedProof::from_nodes(vec![chunk(1), chunk(*index)]);
+            assert_eq!(
+                Proof::<S>::try_from(proof).err(),
+                Some(Error::InvalidIndex(*index))
+            );
+        }
+
+        // Nodes below the chunk of `a` or below `b`'s len leaf can not exist
+        for index in &[3, 13, 29] {
+            let proof = SerializedProof::from_nodes(vec![chunk(*index), chunk(*index + 1)]);
+            assert_eq!(
+                Proof::<S>::try_from(proof).err(),
+                Some(Error::InvalidIndex(*index))
+            );
+        }
+
+        // Leaves of `b` can be loaded without the rest of the list
+        let mut p =
+            Proof::<S>::try_from(SerializedProof::from_nodes(vec![chunk(25), chunk(26)])).unwrap();
+        assert_eq!(p.fill(), Ok(()));
+    }
+
+    #[test]
+    fn proof_from_value() {
+        let value = S {
+            a: U256::from(42),
+            b: VariableList::new(vec![0, 1, 2]).unwrap(),
+        };
+
+        let p = Proof::<S>::from_value(&value);
+
+        let mut arr = [0_u8; 160];
+        arr[0] = 2;
+        arr[48] = 1;
+        arr[96] = 3;
+        arr[128] = 42;
+        arr[64..96].copy_from_slice(hash_children(&Chunk::default(), &Chunk::default()).as_ref());
+
+        let sp = SerializedProof {
+            indices: vec![24, 23, 12, 6, 1],
+            chunks: arr.to_vec(),
+        };
+
+        assert_eq!(p.extract(b(2)), Ok(sp.clone()));
+
+        // A partial proof built from the extracted nodes has the same root
+        let mut partial = Proof::<S>::new(sp);
+        assert_eq!(partial.fill(), Ok(()));
+        assert_eq!(partial.root(), p.root());
+        assert!(p.is_valid(p.root().unwrap()));
+
+        let mut expected = vec![0; 16];
+        expected[0] = 2;
+        assert_eq!(p.get_bytes(b(2)), Ok(expected));
+    }
+
+    #[test]
+    fn merge_partial_proofs() {
+        let full = Proof::<S>::from_value(&s(vec![0, 1, 2, 3, 4, 5]));
+        let root = full.root().unwrap();
+
+        let mut left = Proof::<S>::new(full.extract(b(0)).unwrap());
+        let mut right = Proof::<S>::new(full.extract(b(5)).unwrap());
+        assert_eq!(left.fill(), Ok(()));
+        assert_eq!(right.fill(), Ok(()));
+
+        assert_eq!(left.merge(right), Ok(()));
+        assert!(left.is_valid(root));
+        assert_eq!(left.get_bytes(b(0)), full.get_bytes(b(0)));
+        assert_eq!(left.get_bytes(b(5)), full.get_bytes(b(5)));
+
+        // Check for `Error::ChunkConflict(_)`
+        let mut other = Proof::<S>::new(full.extract(b(5)).unwrap());
+        assert_eq!(other.fill(), Ok(()));
+        assert_eq!(other.set_bytes(b(4), vec![9; 16]), Ok(()));
+        assert_eq!(left.merge(other), Err(Error::ChunkConflict(0)));
+        assert!(left.is_valid(root));
+    }
+
+    #[test]
+    fn prune_to_paths() {
+        let mut p = Proof::<S>::from_value(&s(vec![0, 1, 2, 3, 4, 5]));
+        let root = p.root().unwrap();
+        let expected = p.extract(b(0)).unwrap();
+
+        assert_eq!(p.prune(&[b(0)]), Ok(()));
+
+        assert!(p.is_valid(root));
+        assert_eq!(p.extract(b(0)), Ok(expected));
+
+        // Helpers are kept while nodes outside the proof are removed
+        assert!(p.get_bytes(b(2)).is_ok());
+        assert_eq!(p.get_bytes(b(4)), Err(Error::ChunkNotLoaded(25)));
+        assert_eq!(p.get_bytes(b(6)), Err(Error::ChunkNotLoaded(26)));
+
+        // Check for `Error::InvalidPath(_)`
+        assert_eq!(
+            p.prune(&[vec![PathElement::from_ident_str("c")]]),
+            Err(Error::InvalidPath(PathElement::from_ident_str("c")))
+        );
+    }
+
+    #[test]
+    fn implied_zero_subtrees() {
+        type B = VariableList<u128, U8>;
+
+        let full = Proof::<B>::from_value(&B::new(vec![1, 2]).unwrap());
+        let root = full.root().unwrap();
+
+        // Only the first leaf and the length are needed, the padding of the list is implied
+        let mut arr = vec![0; 64];
+        arr[0] = 1;
+        arr[16] = 2;
+        arr[32] = 2;
+
+        let sp = SerializedProof {
+            indices: vec![7, 2],
+            chunks: arr,
+        };
+
+        let mut p = Proof::<B>::new(sp);
+        assert_eq!(p.fill(), Ok(()));
+        assert_eq!(p.verify(&root), Ok(()));
+        assert_eq!(
+            p.get_bytes(vec![PathElement::Index(1)]),
+            Ok(2_u128.to_le_bytes().to_vec())
+        );
+        assert_eq!(
+            p.extract(vec![PathElement::Index(0)]),
+            full.extract(vec![PathElement::Index(0)])
+        );
+
+        // Padding that is not a sibling of a loaded node is not implied
+        assert_eq!(
+            p.get_bytes(vec![PathElement::Index(5)]),
+            Err(Error::ChunkNotLoaded(9))
+        );
+    }
+
+    #[test]
+    fn strict_list_lengths() {
+        let mut p = s_proof();
+
+        assert_eq!(p.len(vec![PathElement::from_ident_str("b")]), Ok(3));
+        assert_eq!(
+            p.len(vec![PathElement::from_ident_str("a")]),
+            Err(Error::InvalidPath(PathElement::from_ident_str("len")))
+        );
+
+        // Positions beyond the length are only rejected in strict mode
+        assert_eq!(p.get_bytes(b(3)), Ok(vec![0; 16]));
+
+        p.set_strict_lengths(true);
+        assert_eq!(p.get_bytes(b(2)), Ok(3_u128.to_le_bytes().to_vec()));
+
+        // Check for `Error::IndexBeyondLength(_, _)`
+        assert_eq!(p.get_bytes(b(3)), Err(Error::IndexBeyondLength(3, 3)));
+        assert_eq!(
+            p.set_bytes(b(5), vec![0; 16]),
+            Err(Error::IndexBeyondLength(5, 3))
+        );
+        assert_eq!(
+            p.extract_many(&[b(0), b(7)]).err(),
+            Some(Error::IndexBeyondLength(7, 3))
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let root = |values: Vec<u128>| Proof::<S>::from_value(&s(values)).root();
+        let list = || vec![PathElement::from_ident_str("b")];
+
+        let mut p = Proof::<S>::from_value(&s(vec![1, 2, 3, 4, 5, 6, 7]));
+
+        assert_eq!(p.pop(list()), Ok(7_u128.to_le_bytes().to_vec()));
+        assert_eq!(p.len(list()), Ok(6));
+        assert_eq!(p.root(), root(vec![1, 2, 3, 4, 5, 6]));
+
+        assert_eq!(p.push(list(), 8_u128.to_le_bytes().to_vec()), Ok(()));
+        assert_eq!(p.push(list(), 9_u128.to_le_bytes().to_vec()), Ok(()));
+        assert_eq!(p.len(list()), Ok(8));
+        assert_eq!(p.root(), root(vec![1, 2, 3, 4, 5, 6, 8, 9]));
+
+        // Check for `Error::IndexOutOfBounds(_)`
+        assert_eq!(
+            p.push(list(), 10_u128.to_le_bytes().to_vec()),
+            Err(Error::IndexOutOfBounds(8))
+        );
+
+        // Check for `Error::SizeMismatch(_, _)`
+        let mut p = Proof::<S>::from_value(&s(vec![1]));
+        assert_eq!(
+            p.push(list(), vec![2; 32]),
+            Err(Error::SizeMismatch(16, 32))
+        );
+
+        // Check for `Error::EmptyList()`
+        let mut p = Proof::<S>::from_value(&s(vec![]));
+        assert_eq!(p.pop(list()), Err(Error::EmptyList()));
+
+        // Check for `Error::SiblingNotLoaded(_)`
+        let mut arr = vec![0; 96];
+        arr[64] = 7;
+
+        let mut p = Proof::<S>::new(SerializedProof {
+            indices: vec![26, 25, 6],
+            chunks: arr,
+        });
+        assert_eq!(p.pop(list()), Err(Error::SiblingNotLoaded(11)));
+        assert_eq!(p.len(list()), Ok(7));
+    }
+
+    #[test]
+    fn persisted_proofs() {
+        use std::io::Write;
+
+        let mut memory = s_proof();
+        let extracted = memory.extract(b(2)).unwrap();
+
+        let file = std::env::temp_dir().join(format!("proof-log-nested-{}", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+
+        let mut p = Proof::<S>::open(&file).unwrap();
+        assert_eq!(p.root(), Err(Error::RootNotLoaded()));
+        assert_eq!(p.load(extracted), Ok(()));
+        assert_eq!(p.fill(), Ok(()));
+        p.commit().unwrap();
+        let root = p.root().unwrap();
+
+        // Writes are only restored once they are committed
+        assert_eq!(p.set_bytes(b(2), vec![9; 16]), Ok(()));
+        drop(p);
+
+        let mut p = Proof::<S>::open(&file).unwrap();
+        assert_eq!(p.verify(&root), Ok(()));
+        assert_eq!(p.get_bytes(b(2)), Ok(3_u128.to_le_bytes().to_vec()));
+
+        assert_eq!(p.set_bytes(b(2), vec![9; 16]), Ok(()));
+        assert_eq!(memory.set_bytes(b(2), vec![9; 16]), Ok(()));
+        p.commit().unwrap();
+        drop(p);
+
+        // A corrupted tail falls back to the last consistent root
+        let mut f = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&file)
+            .unwrap();
+        f.write_all(&[0xff; 100]).unwrap();
+        drop(f);
+
+        let mut p = Proof::<S>::open(&file).unwrap();
+        assert_eq!(p.root(), memory.root());
+        assert_eq!(p.get_bytes(b(2)), Ok(vec![9; 16]));
+
+        // Compacting keeps only the current nodes
+        let size = std::fs::metadata(&file).unwrap().len();
+        p.compact().unwrap();
+        assert!(std::fs::metadata(&file).unwrap().len() < size);
+        drop(p);
+
+        let p = Proof::<S>::open(&file).unwrap();
+        assert_eq!(p.root(), memory.root());
+        assert_eq!(p.extract(b(2)), memory.extract(b(2)));
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn diff_and_patch() {
+        let mut old = s_proof();
+        let mut new = s_proof();
+        assert_eq!(new.set_bytes(b(2), vec![9; 16]), Ok(()));
+
+        let mut expected = BTreeMap::new();
+        expected.insert(0, Some(vec![]));
+        expected.insert(2, Some(vec![PathElement::from_ident_str("b")]));
+        expected.insert(5, None);
+        expected.insert(11, None);
+        expected.insert(24, Some(b(2)));
+
+        assert_eq!(old.diff(&new), Ok(expected.clone()));
+        assert_eq!(new.diff(&old), Ok(expected));
+
+        // Patches survive encoding and move the old proof to the new root
+        let patch = old.patch(&new).unwrap();
+        assert_eq!(patch.nodes.indices, vec![0, 2, 5, 11, 24]);
+        assert_eq!(
+            Patch::from_ssz_bytes(&patch.as_ssz_bytes()),
+            Ok(patch.clone())
+        );
+
+        let mut invalid = patch.clone();
+        invalid.root = Chunk::default();
+
+        assert_eq!(
+            old.apply(&invalid),
+            Err(Error::RootMismatch(Chunk::default(), patch.root))
+        );
+        assert!(old.diff(&s_proof()).unwrap().is_empty());
+
+        assert_eq!(old.apply(&patch), Ok(()));
+        assert!(old.diff(&new).unwrap().is_empty());
+        assert_eq!(old.get_bytes(b(2)), Ok(vec![9; 16]));
+
+        // Nodes which are missing from the newer proof are removed
+        let mut partial =
+            Proof::<S>::new(new.extract(vec![PathElement::from_ident_str("a")]).unwrap());
+        assert_eq!(partial.fill(), Ok(()));
+
+        let patch = new.patch(&partial).unwrap();
+        assert_eq!(patch.removed, vec![5, 6, 11, 12, 23, 24]);
+        assert_eq!(new.apply(&patch), Ok(()));
+        assert!(new.diff(&partial).unwrap().is_empty());
+    }
+}
@@ -1,8 +1,21 @@
 use super::{NodeIndex, BYTES_PER_CHUNK};
-use ssz_derive::{Decode, Encode};
+use crate::chunk::Chunk;
+use crate::error::Error;
+use crate::generalized_index::GeneralizedIndex;
+use crate::tree_arithmetic::zeroed::{expand_tree_index, get_helper_indices};
+use ssz::{Decode, DecodeError, Encode, SszDecoderBuilder, SszEncoder, BYTES_PER_LENGTH_OFFSET};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 
 /// A serializable represenation of a `Proof`.
-#[derive(Clone, Debug, Decode, Encode, Default)]
+///
+/// In SSZ a proof is the container `(indices: List[uint64], chunks: List[uint8])`. This is an
+/// extension of the specification: `NodeIndex` is 128 bits wide, so if any index does not fit in
+/// a `uint64` a third field `upper: List[uint64]` holding the upper 64 bits of every index is
+/// appended. Decoders tell the two layouts apart by the first offset, which is `8` for two fields
+/// and `12` for three, so decoders which only know the two field container reject deep proofs
+/// rather than misreading them. `CompactProof` is encoded the same way.
+#[derive(Clone, Debug, Default)]
 pub struct SerializedProof {
     pub indices: Vec<NodeIndex>,
     pub chunks: Vec<u8>, // vec<bytes32>
@@ -14,15 +27,504 @@ impl PartialEq for SerializedProof {
             return false;
         }
 
-        self.indices.iter().enumerate().fold(true, |s, (i, j)| {
-            let chunk = self.chunks[i * BYTES_PER_CHUNK];
+        let other: HashMap<NodeIndex, Chunk> = other.nodes().collect();
 
-            if let Some(other_index) = other.indices.iter().position(|e| e == j) {
-                let other_chunk = other.chunks[other_index * BYTES_PER_CHUNK];
-                return s && chunk == other_chunk;
+        self.nodes()
+            .all(|(index, chunk)| other.get(&index) == Some(&chunk))
+    }
+}
+
+impl SerializedProof {
+    /// Builds a `SerializedProof` from pairs of node indices and their chunks.
+    pub fn from_nodes<I: IntoIterator<Item = (NodeIndex, Chunk)>>(nodes: I) -> Self {
+        let mut ret = Self::default();
+
+        for (index, chunk) in nodes {
+            ret.indices.push(index);
+            ret.chunks.extend_from_slice(chunk.as_ref());
+        }
+
+        ret
+    }
+
+    /// Returns the chunk of the node at `indices[position]`, if the proof contains it.
+    pub fn chunk(&self, position: usize) -> Option<Chunk> {
+        let bytes = self
+            .chunks
+            .get(position * BYTES_PER_CHUNK..(position + 1) * BYTES_PER_CHUNK)?;
+
+        Chunk::try_from(bytes).ok()
+    }
+
+    /// Iterates over the index and chunk of every node in the proof. Indices without a chunk
+    /// are skipped.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeIndex, Chunk)> + '_ {
+        self.indices
+            .iter()
+            .enumerate()
+            .filter_map(move |(position, index)| Some((*index, self.chunk(position)?)))
+    }
+
+    /// Converts a proof whose indices are 1-indexed generalized indices, as used by the
+    /// specification, into a proof with zero-indexed `NodeIndex` values.
+    pub fn from_generalized(proof: SerializedProof) -> Result<Self, Error> {
+        let indices = proof
+            .indices
+            .iter()
+            .map(|i| GeneralizedIndex::new(*i).map(GeneralizedIndex::to_node_index))
+            .collect::<Result<_, _>>()?;
+
+        Ok(SerializedProof {
+            indices,
+            chunks: proof.chunks,
+        })
+    }
+
+    /// Converts the proof into one whose indices are 1-indexed generalized indices, as used by
+    /// the specification.
+    pub fn to_generalized(&self) -> Result<Self, Error> {
+        let indices = self
+            .indices
+            .iter()
+            .map(|i| GeneralizedIndex::from_node_index(*i).map(GeneralizedIndex::get))
+            .collect::<Result<_, _>>()?;
+
+        Ok(SerializedProof {
+            indices,
+            chunks: self.chunks.clone(),
+        })
+    }
+}
+
+/// A compact representation of a `SerializedProof` that follows the multiproof layout of the
+/// specification.
+///
+/// Only the indices of the leaves are included. `chunks` contains the chunks of the leaves
+/// followed by the chunks of their helper nodes, in the order returned by `get_helper_indices`.
+/// Nodes that can be calculated from the leaves and helpers are omitted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactProof {
+    pub leaves: Vec<NodeIndex>,
+    pub chunks: Vec<u8>, // vec<bytes32>
+}
+
+impl CompactProof {
+    /// Builds a `CompactProof` for `leaves` using the chunks in `proof`.
+    ///
+    /// Returns `Error::InvalidIndex` if the leaves do not have well defined helper nodes, see
+    /// `indices`.
+    pub fn from_serialized(proof: &SerializedProof, leaves: &[NodeIndex]) -> Result<Self, Error> {
+        check_leaves(leaves)?;
+
+        let nodes: HashMap<NodeIndex, Chunk> = proof.nodes().collect();
+        let mut chunks: Vec<u8> = vec![];
+
+        for index in leaves.iter().chain(get_helper_indices(leaves).iter()) {
+            let chunk = nodes.get(index).ok_or(Error::ChunkNotLoaded(*index))?;
+
+            chunks.extend_from_slice(chunk.as_ref());
+        }
+
+        Ok(Self {
+            leaves: leaves.to_vec(),
+            chunks,
+        })
+    }
+
+    /// Returns the indices of the leaves followed by the indices of their helper nodes.
+    ///
+    /// Returns `Error::InvalidIndex` if a leaf is `NodeIndex::MAX`, which has no general index,
+    /// if a leaf is listed more than once or if a leaf is an ancestor of another leaf.
+    pub fn indices(&self) -> Result<Vec<NodeIndex>, Error> {
+        check_leaves(&self.leaves)?;
+
+        let mut ret = self.leaves.clone();
+        ret.extend(get_helper_indices(&self.leaves));
+        Ok(ret)
+    }
+}
+
+/// Ensures that the helper nodes of `leaves` are well defined, see `CompactProof::indices`.
+pub(crate) fn check_leaves(leaves: &[NodeIndex]) -> Result<(), Error> {
+    let mut seen: HashSet<NodeIndex> = HashSet::new();
+
+    for leaf in leaves {
+        if *leaf == NodeIndex::MAX || !seen.insert(*leaf) {
+            return Err(Error::InvalidIndex(*leaf));
+        }
+    }
+
+    for leaf in leaves {
+        let mut index = *leaf;
+
+        while index > 0 {
+            index = expand_tree_index(index).2;
+
+            if seen.contains(&index) {
+                return Err(Error::InvalidIndex(index));
             }
+        }
+    }
+
+    Ok(())
+}
+
+impl TryFrom<CompactProof> for SerializedProof {
+    type Error = Error;
+
+    fn try_from(proof: CompactProof) -> Result<Self, Self::Error> {
+        let indices = proof.indices()?;
+        let expected = indices.len() * BYTES_PER_CHUNK;
 
-            false
+        if proof.chunks.len() != expected {
+            return Err(Error::ChunkLengthMismatch(expected, proof.chunks.len()));
+        }
+
+        Ok(SerializedProof {
+            indices,
+            chunks: proof.chunks,
         })
     }
 }
+
+/// The changes which move a `Proof` from one root to another, as produced by `Proof::patch`.
+///
+/// `nodes` contains the nodes which are new or whose chunks changed, and `removed` contains the
+/// indices of the nodes which no longer exist. Applying the patch with `Proof::apply` fails unless
+/// the resulting tree is valid for `root`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    pub root: Chunk,
+    pub nodes: SerializedProof,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::indices"))]
+    pub removed: Vec<NodeIndex>,
+}
+
+/// Implements SSZ encoding for a proof container made up of a list of `NodeIndex` values and a
+/// list of bytes, i.e. `(indices: List[uint64], chunks: List[uint8])`.
+///
+/// If an index does not fit in a `uint64`, a third field holding the upper 64 bits of every index
+/// is appended, i.e. `(.., upper: List[uint64])`. Decoders which only know the two field container
+/// reject these proofs, since the first offset no longer matches.
+macro_rules! impl_ssz_for_proof {
+    ($type: ident, $indices: ident) => {
+        impl Encode for $type {
+            fn is_ssz_fixed_len() -> bool {
+                false
+            }
+
+            fn ssz_append(&self, buf: &mut Vec<u8>) {
+                let (lower, upper) = split_indices(&self.$indices);
+                let fields = if upper.is_some() { 3 } else { 2 };
+
+                let mut encoder = SszEncoder::container(buf, fields * BYTES_PER_LENGTH_OFFSET);
+                encoder.append(&lower);
+                encoder.append(&self.chunks);
+
+                if let Some(upper) = upper {
+                    encoder.append(&upper);
+                }
+
+                encoder.finalize();
+            }
+        }
+
+        impl Decode for $type {
+            fn is_ssz_fixed_len() -> bool {
+                false
+            }
+
+            fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+                let wide = has_upper_indices(bytes, 0, 2);
+                let mut builder = SszDecoderBuilder::new(bytes);
+
+                builder.register_type::<Vec<u64>>()?;
+                builder.register_type::<Vec<u8>>()?;
+
+                if wide {
+                    builder.register_type::<Vec<u64>>()?;
+                }
+
+                let mut decoder = builder.build()?;
+                let lower = decoder.decode_next()?;
+                let chunks: Vec<u8> = decoder.decode_next()?;
+                let upper = if wide {
+                    Some(decoder.decode_next()?)
+                } else {
+                    None
+                };
+
+                Ok(Self {
+                    $indices: join_indices(lower, upper)?,
+                    chunks,
+                })
+            }
+        }
+    };
+}
+
+impl_ssz_for_proof!(SerializedProof, indices);
+impl_ssz_for_proof!(CompactProof, leaves);
+
+/// Encoded as `(root: Bytes32, nodes: SerializedProof, removed: List[uint64])`, with the upper
+/// 64 bits of the removed indices appended as for proofs if any of them needs them.
+impl Encode for Patch {
+    fn is_ssz_fixed_len() -> bool {
+        false
+    }
+
+    fn ssz_append(&self, buf: &mut Vec<u8>) {
+        let (lower, upper) = split_indices(&self.removed);
+        let fields = if upper.is_some() { 3 } else { 2 };
+
+        let mut encoder =
+            SszEncoder::container(buf, BYTES_PER_CHUNK + fields * BYTES_PER_LENGTH_OFFSET);
+        encoder.append(&self.root);
+        encoder.append(&self.nodes);
+        encoder.append(&lower);
+
+        if let Some(upper) = upper {
+            encoder.append(&upper);
+        }
+
+        encoder.finalize();
+    }
+}
+
+impl Decode for Patch {
+    fn is_ssz_fixed_len() -> bool {
+        false
+    }
+
+    fn from_ssz_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let wide = has_upper_indices(bytes, BYTES_PER_CHUNK, 2);
+        let mut builder = SszDecoderBuilder::new(bytes);
+
+        builder.register_type::<Chunk>()?;
+        builder.register_type::<SerializedProof>()?;
+        builder.register_type::<Vec<u64>>()?;
+
+        if wide {
+            builder.register_type::<Vec<u64>>()?;
+        }
+
+        let mut decoder = builder.build()?;
+        let root = decoder.decode_next()?;
+        let nodes = decoder.decode_next()?;
+        let lower = decoder.decode_next()?;
+        let upper = if wide {
+            Some(decoder.decode_next()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            root,
+            nodes,
+            removed: join_indices(lower, upper)?,
+        })
+    }
+}
+
+/// Splits `indices` into their lower 64 bits and, if any index does not fit in a `u64`, their
+/// upper 64 bits.
+fn split_indices(indices: &[NodeIndex]) -> (Vec<u64>, Option<Vec<u64>>) {
+    let lower = indices.iter().map(|i| *i as u64).collect();
+
+    if indices.iter().all(|i| *i <= NodeIndex::from(u64::MAX)) {
+        (lower, None)
+    } else {
+        (
+            lower,
+            Some(indices.iter().map(|i| (*i >> 64) as u64).collect()),
+        )
+    }
+}
+
+/// Joins the halves of the indices produced by `split_indices`.
+fn join_indices(lower: Vec<u64>, upper: Option<Vec<u64>>) -> Result<Vec<NodeIndex>, DecodeError> {
+    let upper = match upper {
+        Some(upper) => upper,
+        None => return Ok(lower.into_iter().map(NodeIndex::from).collect()),
+    };
+
+    if upper.len() != lower.len() {
+        return Err(DecodeError::BytesInvalid(format!(
+            "{} upper halves for {} indices",
+            upper.len(),
+            lower.len()
+        )));
+    }
+
+    // Only one encoding of each proof is accepted
+    if upper.iter().all(|u| *u == 0) {
+        return Err(DecodeError::BytesInvalid(
+            "upper halves given for indices which fit in a uint64".to_string(),
+        ));
+    }
+
+    Ok(lower
+        .into_iter()
+        .zip(upper)
+        .map(|(l, u)| NodeIndex::from(u) << 64 | NodeIndex::from(l))
+        .collect())
+}
+
+/// Returns `true` if the container in `bytes` has the extra field holding the upper halves of its
+/// indices. The container has `fixed` bytes of fixed size fields followed by `offsets` offsets,
+/// so the first offset, which is the length of the fixed part, tells whether one more follows.
+fn has_upper_indices(bytes: &[u8], fixed: usize, offsets: usize) -> bool {
+    let mut offset = [0; BYTES_PER_LENGTH_OFFSET];
+
+    match bytes.get(fixed..fixed + BYTES_PER_LENGTH_OFFSET) {
+        Some(bytes) => offset.copy_from_slice(bytes),
+        None => return false,
+    }
+
+    u32::from_le_bytes(offset) as usize == fixed + (offsets + 1) * BYTES_PER_LENGTH_OFFSET
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{b, s, S};
+    use crate::verify::verify_merkle_multiproof;
+    use crate::Proof;
+
+    #[test]
+    fn ssz_indices() {
+        let proof = SerializedProof::from_nodes(vec![(1, Chunk([1; BYTES_PER_CHUNK]))]);
+        let bytes = proof.as_ssz_bytes();
+
+        // Indices which fit are encoded as a list of `uint64`s
+        let mut expected = vec![8, 0, 0, 0, 16, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(&[1; BYTES_PER_CHUNK]);
+        assert_eq!(bytes, expected);
+        assert_eq!(SerializedProof::from_ssz_bytes(&bytes), Ok(proof));
+
+        // Wider indices append their upper halves
+        let wide = SerializedProof::from_nodes(vec![
+            (1, Chunk::default()),
+            (1 << 100 | 3, Chunk::default()),
+        ]);
+        let bytes = wide.as_ssz_bytes();
+        assert_eq!(&bytes[..4], &[12, 0, 0, 0]);
+        assert_eq!(SerializedProof::from_ssz_bytes(&bytes), Ok(wide));
+
+        let compact = CompactProof {
+            leaves: vec![NodeIndex::MAX - 1],
+            chunks: vec![0; BYTES_PER_CHUNK],
+        };
+        assert_eq!(
+            CompactProof::from_ssz_bytes(&compact.as_ssz_bytes()),
+            Ok(compact)
+        );
+
+        let patch = Patch {
+            root: Chunk([2; BYTES_PER_CHUNK]),
+            nodes: SerializedProof::from_nodes(vec![(1 << 70, Chunk::default())]),
+            removed: vec![4, 1 << 90],
+        };
+        assert_eq!(Patch::from_ssz_bytes(&patch.as_ssz_bytes()), Ok(patch));
+
+        // Upper halves are rejected if they are all zero, or if one is missing
+        let mut buf = vec![];
+        let mut encoder = SszEncoder::container(&mut buf, 3 * BYTES_PER_LENGTH_OFFSET);
+        encoder.append(&vec![1_u64]);
+        encoder.append(&vec![0_u8; BYTES_PER_CHUNK]);
+        encoder.append(&vec![0_u64]);
+        encoder.finalize();
+        assert!(SerializedProof::from_ssz_bytes(&buf).is_err());
+
+        let mut buf = vec![];
+        let mut encoder = SszEncoder::container(&mut buf, 3 * BYTES_PER_LENGTH_OFFSET);
+        encoder.append(&vec![1_u64, 2]);
+        encoder.append(&vec![0_u8; 2 * BYTES_PER_CHUNK]);
+        encoder.append(&vec![1_u64]);
+        encoder.finalize();
+        assert!(SerializedProof::from_ssz_bytes(&buf).is_err());
+    }
+
+    #[test]
+    fn compact_proofs() {
+        let p = Proof::<S>::from_value(&s(vec![0, 1, 2, 3, 4, 5]));
+        let root = p.root().unwrap();
+        let paths = [b(0), b(5)];
+
+        let compact = p.extract_compact(&paths).unwrap();
+        let serialized = p.extract_many(&paths).unwrap();
+
+        assert_eq!(compact.leaves, vec![23, 25]);
+        assert_eq!(compact.indices(), Ok(serialized.indices.clone()));
+        assert_eq!(compact.chunks, serialized.chunks);
+        assert!(compact.as_ssz_bytes().len() < serialized.as_ssz_bytes().len());
+        assert_eq!(
+            CompactProof::from_ssz_bytes(&compact.as_ssz_bytes()),
+            Ok(compact.clone())
+        );
+
+        // Roundtrip through the serialized format
+        assert_eq!(
+            SerializedProof::try_from(compact.clone()),
+            Ok(serialized.clone())
+        );
+        assert_eq!(
+            CompactProof::from_serialized(&serialized, &compact.leaves),
+            Ok(compact.clone())
+        );
+
+        // Nodes that are not needed for the leaves are dropped when compacting
+        let pruned = p.extract_many(&[b(0), b(5), b(2)]).unwrap();
+        assert_eq!(
+            CompactProof::from_serialized(&pruned, &[23, 25]),
+            Ok(compact.clone())
+        );
+
+        // The compact proof can be verified directly
+        let leaves: Vec<GeneralizedIndex> = compact
+            .leaves
+            .iter()
+            .map(|i| GeneralizedIndex::from_node_index(*i).unwrap())
+            .collect();
+        let chunks: Vec<Chunk> = compact
+            .chunks
+            .chunks(BYTES_PER_CHUNK)
+            .map(|c| Chunk::try_from(c).unwrap())
+            .collect();
+        assert_eq!(
+            verify_merkle_multiproof(&chunks[..2], &chunks[2..], &leaves, &root),
+            Ok(())
+        );
+
+        // Leaves without well defined helper nodes are rejected when decoding
+        for (leaves, index) in &[
+            (vec![NodeIndex::MAX], NodeIndex::MAX),
+            (vec![23, 23], 23),
+            (vec![23, 2], 2),
+        ] {
+            let malicious = CompactProof {
+                leaves: leaves.clone(),
+                chunks: vec![0; 32 * leaves.len()],
+            };
+            let decoded = CompactProof::from_ssz_bytes(&malicious.as_ssz_bytes()).unwrap();
+
+            assert_eq!(
+                SerializedProof::try_from(decoded),
+                Err(Error::InvalidIndex(*index))
+            );
+            assert_eq!(
+                CompactProof::from_serialized(&serialized, leaves),
+                Err(Error::InvalidIndex(*index))
+            );
+        }
+
+        // Check for `Error::ChunkLengthMismatch(_, _)`
+        let mut truncated = compact;
+        truncated.chunks.truncate(64);
+        assert_eq!(
+            SerializedProof::try_from(truncated),
+            Err(Error::ChunkLengthMismatch(192, 64))
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use super::{NodeIndex, BYTES_PER_CHUNK};
+use crate::chunk::Chunk;
+use crate::path::{Path, PathElement};
+use crate::ser::{CompactProof, SerializedProof};
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Chunks are serialized as `0x` prefixed hex strings.
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = self.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&format!("0x{}", hex))
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let hex = s
+            .strip_prefix("0x")
+            .ok_or_else(|| de::Error::custom("chunk is missing the 0x prefix"))?;
+
+        if hex.len() != 2 * BYTES_PER_CHUNK || !hex.is_ascii() {
+            return Err(de::Error::invalid_length(
+                hex.len() / 2,
+                &"32 hex encoded bytes",
+            ));
+        }
+
+        let mut ret = Chunk::default();
+
+        for (i, byte) in ret.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| de::Error::custom(format!("invalid hex in chunk: {}", s)))?;
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Path elements are serialized as strings, e.g. `"len"` or `"3"`. Positions may also be
+/// deserialized from integers.
+impl Serialize for PathElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathElement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PathElementVisitor;
+
+        impl<'de> Visitor<'de> for PathElementVisitor {
+            type Value = PathElement;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("an identifier or a position")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<PathElement, E> {
+                Ok(PathElement::from(value))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<PathElement, E> {
+                Ok(PathElement::Index(value))
+            }
+        }
+
+        deserializer.deserialize_any(PathElementVisitor)
+    }
+}
+
+/// Paths are serialized as strings, e.g. `"messages[3].timestamp"`.
+impl Serialize for Path {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Path {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| de::Error::custom(format!("{:?}", e)))
+    }
+}
+
+/// A `NodeIndex` serialized as a decimal string, since many JSON parsers lose the precision of
+/// integers above `2^53`. Integers which fit in a `u64` are also accepted when deserializing.
+struct DecimalIndex(NodeIndex);
+
+impl Serialize for DecimalIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for DecimalIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecimalIndexVisitor;
+
+        impl<'de> Visitor<'de> for DecimalIndexVisitor {
+            type Value = DecimalIndex;
+
+            fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.write_str("a node index as a decimal string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<DecimalIndex, E> {
+                value
+                    .parse()
+                    .map(DecimalIndex)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<DecimalIndex, E> {
+                Ok(DecimalIndex(value.into()))
+            }
+        }
+
+        deserializer.deserialize_any(DecimalIndexVisitor)
+    }
+}
+
+/// Serializes a `NodeIndex` as a decimal string, for use with `#[serde(with = "..")]`.
+pub(crate) mod index {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(index: &NodeIndex, serializer: S) -> Result<S::Ok, S::Error> {
+        DecimalIndex(*index).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NodeIndex, D::Error> {
+        Ok(DecimalIndex::deserialize(deserializer)?.0)
+    }
+}
+
+/// Serializes a list of `NodeIndex` values as decimal strings, for use with
+/// `#[serde(with = "..")]`.
+pub(crate) mod indices {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        indices: &[NodeIndex],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(indices.iter().map(|i| DecimalIndex(*i)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<NodeIndex>, D::Error> {
+        Ok(Vec::<DecimalIndex>::deserialize(deserializer)?
+            .into_iter()
+            .map(|i| i.0)
+            .collect())
+    }
+}
+
+/// Splits `bytes` into chunks, failing if they do not fill a whole number of chunks.
+fn split_chunks<E: ser::Error>(bytes: &[u8]) -> Result<Vec<Chunk>, E> {
+    if bytes.len() % BYTES_PER_CHUNK != 0 {
+        return Err(E::custom("chunks are not a multiple of 32 bytes"));
+    }
+
+    Ok(bytes.chunks(BYTES_PER_CHUNK).map(Chunk::padded).collect())
+}
+
+/// Serialized as `{"indices": ["1", ..], "chunks": ["0x..", ..]}`.
+impl Serialize for SerializedProof {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Proof<'a> {
+            #[serde(with = "indices")]
+            indices: &'a [NodeIndex],
+            chunks: Vec<Chunk>,
+        }
+
+        Proof {
+            indices: &self.indices,
+            chunks: split_chunks(&self.chunks)?,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializedProof {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Proof {
+            #[serde(with = "indices")]
+            indices: Vec<NodeIndex>,
+            chunks: Vec<Chunk>,
+        }
+
+        let proof = Proof::deserialize(deserializer)?;
+
+        Ok(Self {
+            indices: proof.indices,
+            chunks: proof.chunks.iter().flat_map(|c| c.to_vec()).collect(),
+        })
+    }
+}
+
+/// Serialized as `{"leaves": ["1", ..], "chunks": ["0x..", ..]}`.
+impl Serialize for CompactProof {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Compact<'a> {
+            #[serde(with = "indices")]
+            leaves: &'a [NodeIndex],
+            chunks: Vec<Chunk>,
+        }
+
+        Compact {
+            leaves: &self.leaves,
+            chunks: split_chunks(&self.chunks)?,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CompactProof {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Compact {
+            #[serde(with = "indices")]
+            leaves: Vec<NodeIndex>,
+            chunks: Vec<Chunk>,
+        }
+
+        let compact = Compact::deserialize(deserializer)?;
+
+        Ok(Self {
+            leaves: compact.leaves,
+            chunks: compact.chunks.iter().flat_map(|c| c.to_vec()).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::Node;
+    use crate::ser::Patch;
+
+    #[test]
+    fn json_roundtrip() {
+        let mut chunks = vec![0; 2 * BYTES_PER_CHUNK];
+        chunks[0] = 0xab;
+        chunks[BYTES_PER_CHUNK] = 1;
+
+        let proof = SerializedProof {
+            indices: vec![1, 2],
+            chunks,
+        };
+
+        let json = format!(
+            r#"{{"indices":["1","2"],"chunks":["0xab{}","0x01{}"]}}"#,
+            "0".repeat(62),
+            "0".repeat(62)
+        );
+
+        assert_eq!(serde_json::to_string(&proof).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<SerializedProof>(&json).unwrap(),
+            proof
+        );
+
+        let patch = Patch {
+            root: Chunk([1; BYTES_PER_CHUNK]),
+            nodes: proof,
+            removed: vec![3],
+        };
+
+        let json = serde_json::to_string(&patch).unwrap();
+        assert!(json.ends_with(r#""removed":["3"]}"#));
+        assert_eq!(serde_json::from_str::<Patch>(&json).unwrap(), patch);
+
+        // Indices keep their precision beyond `2^53`, and integers are still accepted
+        let compact = CompactProof {
+            leaves: vec![NodeIndex::MAX],
+            chunks: vec![0; BYTES_PER_CHUNK],
+        };
+        let json = serde_json::to_string(&compact).unwrap();
+        assert!(json.starts_with(&format!(r#"{{"leaves":["{}"]"#, NodeIndex::MAX)));
+        assert_eq!(
+            serde_json::from_str::<CompactProof>(&json).unwrap(),
+            compact
+        );
+
+        let json = format!(
+            r#"{{"indices":[1,"2"],"chunks":["0x{0}","0x{0}"]}}"#,
+            "0".repeat(64)
+        );
+        assert_eq!(
+            serde_json::from_str::<SerializedProof>(&json)
+                .unwrap()
+                .indices,
+            vec![1, 2]
+        );
+
+        let path: Path = "messages[3].timestamp".parse().unwrap();
+        assert_eq!(
+            serde_json::to_string(&path.elements()).unwrap(),
+            r#"["messages","3","timestamp"]"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Vec<PathElement>>(r#"["messages",3,"timestamp"]"#).unwrap(),
+            path.elements()
+        );
+        assert_eq!(
+            serde_json::to_string(&path).unwrap(),
+            r#""messages[3].timestamp""#
+        );
+
+        let node = Node {
+            index: 23,
+            ident: PathElement::Index(0),
+            size: 16,
+            offset: 0,
+            height: 0,
+            is_list: false,
+        };
+
+        let json = r#"{"index":"23","ident":"0","size":16,"offset":0,"height":0,"is_list":false}"#;
+        assert_eq!(serde_json::to_string(&node).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Node>(json).unwrap(), node);
+    }
+
+    #[test]
+    fn reject_malformed_chunks() {
+        let chunk = |s: &str| serde_json::from_str::<Chunk>(&format!(r#""{}""#, s));
+
+        assert!(chunk(&format!("0x{}", "1".repeat(64))).is_ok());
+        assert!(chunk(&"1".repeat(64)).is_err());
+        assert!(chunk(&format!("0x{}", "1".repeat(62))).is_err());
+        assert!(chunk(&format!("0x{}", "g".repeat(64))).is_err());
+
+        let proof = SerializedProof {
+            indices: vec![1],
+            chunks: vec![0; 31],
+        };
+
+        assert!(serde_json::to_string(&proof).is_err());
+        assert!(serde_json::from_str::<Path>(r#""a[b""#).is_err());
+
+        let json = format!(r#"{{"indices":["-1"],"chunks":["0x{}"]}}"#, "0".repeat(64));
+        assert!(serde_json::from_str::<SerializedProof>(&json).is_err());
+    }
+}
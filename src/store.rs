@@ -0,0 +1,312 @@
+use super::{NodeIndex, BYTES_PER_CHUNK};
+use crate::chunk::Chunk;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Defines the storage used by a `Backend` to map nodes to their chunks.
+///
+/// Stores which keep their chunks outside of memory report failed reads and writes as an
+/// `io::Error`, which the `Backend` returns as `Error::Io`. In-memory stores never fail.
+pub trait NodeStore: Debug + Send + Sync {
+    /// Returns the chunk of the node at `index`, if it is stored.
+    fn get(&self, index: NodeIndex) -> io::Result<Option<Chunk>>;
+
+    /// Stores the chunk of the node at `index` and returns the old value.
+    fn insert(&mut self, index: NodeIndex, chunk: Chunk) -> io::Result<Option<Chunk>>;
+
+    /// Removes the node at `index` and returns its chunk.
+    fn remove(&mut self, index: NodeIndex) -> io::Result<Option<Chunk>>;
+
+    /// Returns `true` if the chunk of the node at `index` is stored.
+    fn contains(&self, index: NodeIndex) -> io::Result<bool> {
+        Ok(self.get(index)?.is_some())
+    }
+
+    /// Iterates over every stored node and its chunk.
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<(NodeIndex, Chunk)>> + '_>;
+
+    /// Returns the index of every stored node.
+    fn indices(&self) -> io::Result<Vec<NodeIndex>> {
+        self.iter().map(|node| Ok(node?.0)).collect()
+    }
+}
+
+impl NodeStore for HashMap<NodeIndex, Chunk> {
+    fn get(&self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        Ok(HashMap::get(self, &index).copied())
+    }
+
+    fn insert(&mut self, index: NodeIndex, chunk: Chunk) -> io::Result<Option<Chunk>> {
+        Ok(HashMap::insert(self, index, chunk))
+    }
+
+    fn remove(&mut self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        Ok(HashMap::remove(self, &index))
+    }
+
+    fn contains(&self, index: NodeIndex) -> io::Result<bool> {
+        Ok(self.contains_key(&index))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<(NodeIndex, Chunk)>> + '_> {
+        Box::new(HashMap::iter(self).map(|(index, chunk)| Ok((*index, *chunk))))
+    }
+
+    fn indices(&self) -> io::Result<Vec<NodeIndex>> {
+        Ok(self.keys().cloned().collect())
+    }
+}
+
+/// Iterates over the nodes in increasing order of their index.
+impl NodeStore for BTreeMap<NodeIndex, Chunk> {
+    fn get(&self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        Ok(BTreeMap::get(self, &index).copied())
+    }
+
+    fn insert(&mut self, index: NodeIndex, chunk: Chunk) -> io::Result<Option<Chunk>> {
+        Ok(BTreeMap::insert(self, index, chunk))
+    }
+
+    fn remove(&mut self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        Ok(BTreeMap::remove(self, &index))
+    }
+
+    fn contains(&self, index: NodeIndex) -> io::Result<bool> {
+        Ok(self.contains_key(&index))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<(NodeIndex, Chunk)>> + '_> {
+        Box::new(BTreeMap::iter(self).map(|(index, chunk)| Ok((*index, *chunk))))
+    }
+
+    fn indices(&self) -> io::Result<Vec<NodeIndex>> {
+        Ok(self.keys().cloned().collect())
+    }
+}
+
+/// A `NodeStore` which keeps the chunks in a file, so that only the position of each chunk in
+/// the file is held in memory. Nodes are iterated in increasing order of their index.
+///
+/// The file is scratch space for a single store: its slots are only meaningful to the store
+/// that wrote them, and the slots of removed nodes are reused by later insertions. Use
+/// `Proof::open` to keep a proof between runs.
+///
+/// # Memory
+/// The chunks are only read from the file when they are needed, but the index of the slot of
+/// every stored node is kept in a `BTreeMap` in memory, along with the list of free slots. Each
+/// node costs a few dozen bytes of memory in place of its 32 byte chunk, so a `FileStore`
+/// reduces the resident memory of a proof rather than bounding it.
+#[derive(Debug)]
+pub struct FileStore {
+    file: Mutex<File>,
+    slots: BTreeMap<NodeIndex, u64>,
+    free: Vec<u64>,
+    len: u64,
+}
+
+impl FileStore {
+    /// Creates an empty store backed by the file at `path`. The file is truncated if it already
+    /// exists, so `path` should point to a temporary file.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+            slots: BTreeMap::new(),
+            free: vec![],
+            len: 0,
+        })
+    }
+
+    /// Reads the chunk stored in `slot`.
+    fn read_slot(&self, slot: u64) -> io::Result<Chunk> {
+        let mut file = self.file.lock().expect("store file to be unlocked");
+        let mut chunk = Chunk::default();
+
+        file.seek(SeekFrom::Start(slot * BYTES_PER_CHUNK as u64))?;
+        file.read_exact(&mut *chunk)?;
+
+        Ok(chunk)
+    }
+
+    /// Writes `chunk` into `slot`.
+    fn write_slot(&self, slot: u64, chunk: &Chunk) -> io::Result<()> {
+        let mut file = self.file.lock().expect("store file to be unlocked");
+
+        file.seek(SeekFrom::Start(slot * BYTES_PER_CHUNK as u64))?;
+        file.write_all(chunk.as_ref())
+    }
+}
+
+/// A failed write leaves the node as it was before the write.
+impl NodeStore for FileStore {
+    fn get(&self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        self.slots
+            .get(&index)
+            .map(|slot| self.read_slot(*slot))
+            .transpose()
+    }
+
+    fn insert(&mut self, index: NodeIndex, chunk: Chunk) -> io::Result<Option<Chunk>> {
+        if let Some(slot) = self.slots.get(&index).cloned() {
+            let old = self.read_slot(slot)?;
+            self.write_slot(slot, &chunk)?;
+            return Ok(Some(old));
+        }
+
+        let slot = self.free.last().cloned().unwrap_or(self.len);
+        self.write_slot(slot, &chunk)?;
+
+        if self.free.pop().is_none() {
+            self.len += 1;
+        }
+        self.slots.insert(index, slot);
+
+        Ok(None)
+    }
+
+    fn remove(&mut self, index: NodeIndex) -> io::Result<Option<Chunk>> {
+        let slot = match self.slots.get(&index) {
+            Some(slot) => *slot,
+            None => return Ok(None),
+        };
+
+        let chunk = self.read_slot(slot)?;
+        self.slots.remove(&index);
+        self.free.push(slot);
+
+        Ok(Some(chunk))
+    }
+
+    fn contains(&self, index: NodeIndex) -> io::Result<bool> {
+        Ok(self.slots.contains_key(&index))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = io::Result<(NodeIndex, Chunk)>> + '_> {
+        Box::new(
+            self.slots
+                .iter()
+                .map(move |(index, slot)| Ok((*index, self.read_slot(*slot)?))),
+        )
+    }
+
+    fn indices(&self) -> io::Result<Vec<NodeIndex>> {
+        Ok(self.slots.keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::hasher::Sha256Hasher;
+    use crate::test_utils::{b, s, s_proof, S};
+    use crate::Proof;
+
+    #[test]
+    fn reuse_file_slots() {
+        let path = std::env::temp_dir().join(format!("proof-store-{}", std::process::id()));
+        let mut store = FileStore::create(&path).unwrap();
+
+        assert_eq!(store.insert(5, Chunk([5; BYTES_PER_CHUNK])).unwrap(), None);
+        assert_eq!(store.insert(3, Chunk([3; BYTES_PER_CHUNK])).unwrap(), None);
+        assert_eq!(
+            store.insert(5, Chunk([6; BYTES_PER_CHUNK])).unwrap(),
+            Some(Chunk([5; BYTES_PER_CHUNK]))
+        );
+
+        assert_eq!(store.get(5).unwrap(), Some(Chunk([6; BYTES_PER_CHUNK])));
+        assert_eq!(store.indices().unwrap(), vec![3, 5]);
+
+        // The slot of a removed node is used by the next insertion
+        assert_eq!(store.remove(5).unwrap(), Some(Chunk([6; BYTES_PER_CHUNK])));
+        assert_eq!(store.insert(1, Chunk([1; BYTES_PER_CHUNK])).unwrap(), None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 64);
+
+        assert_eq!(
+            store.iter().collect::<io::Result<Vec<_>>>().unwrap(),
+            vec![
+                (1, Chunk([1; BYTES_PER_CHUNK])),
+                (3, Chunk([3; BYTES_PER_CHUNK]))
+            ]
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn report_io_errors() {
+        let path = std::env::temp_dir().join(format!("proof-store-ro-{}", std::process::id()));
+        std::fs::write(&path, [1; BYTES_PER_CHUNK]).unwrap();
+
+        // A store whose file can not be written
+        let mut store = FileStore {
+            file: Mutex::new(File::open(&path).unwrap()),
+            slots: BTreeMap::new(),
+            free: vec![],
+            len: 0,
+        };
+
+        assert!(store.insert(1, Chunk([1; BYTES_PER_CHUNK])).is_err());
+        assert_eq!(store.indices().unwrap(), vec![]);
+
+        // Chunks beyond the end of the file can not be read
+        store.slots.insert(1, 0);
+        store.slots.insert(2, 1);
+        assert_eq!(store.get(1).unwrap(), Some(Chunk([1; BYTES_PER_CHUNK])));
+        assert_eq!(
+            store.get(2).map_err(|e| e.kind()),
+            Err(io::ErrorKind::UnexpectedEof)
+        );
+
+        // The errors are returned by the proof
+        let mut p = Proof::<S, Sha256Hasher, FileStore>::with_store(store);
+        assert!(matches!(
+            p.fill(),
+            Err(Error::Io(io::ErrorKind::UnexpectedEof, _))
+        ));
+        assert!(matches!(
+            p.load(s_proof().extract(b(2)).unwrap()),
+            Err(Error::Io(..))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn node_stores() {
+        let mut memory = s_proof();
+        let extracted = memory.extract(b(2)).unwrap();
+
+        // Ordered stores merkleize to the same tree
+        let ordered =
+            Proof::<S, Sha256Hasher, BTreeMap<NodeIndex, Chunk>>::from_value(&s(vec![1, 2, 3]));
+        assert_eq!(ordered.root(), memory.root());
+        assert_eq!(ordered.extract(b(2)), Ok(extracted.clone()));
+
+        // Proofs kept in a file behave like proofs kept in memory
+        let file = std::env::temp_dir().join(format!("proof-nested-{}", std::process::id()));
+        let mut p =
+            Proof::<S, Sha256Hasher, FileStore>::with_store(FileStore::create(&file).unwrap());
+
+        assert_eq!(p.load(extracted), Ok(()));
+        assert_eq!(p.fill(), Ok(()));
+        assert_eq!(p.verify(&memory.root().unwrap()), Ok(()));
+
+        assert_eq!(p.set_bytes(b(2), vec![9; 16]), Ok(()));
+        assert_eq!(memory.set_bytes(b(2), vec![9; 16]), Ok(()));
+        assert_eq!(p.get_bytes(b(2)), Ok(vec![9; 16]));
+        assert_eq!(p.root(), memory.root());
+
+        std::fs::remove_file(file).unwrap();
+    }
+}
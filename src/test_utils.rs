@@ -0,0 +1,135 @@
+//! A small nested container shared by the unit tests.
+
+use super::NodeIndex;
+use crate::backend::Backend;
+use crate::error::Error;
+use crate::hasher::{Hasher, Sha256Hasher};
+use crate::merkle_tree_overlay::impls::replace_index;
+use crate::merkle_tree_overlay::{MerkleTreeOverlay, MerkleTreeValue};
+use crate::node::Node;
+use crate::path::PathElement;
+use crate::proof::Proof;
+use crate::store::NodeStore;
+use crate::tree_arithmetic::zeroed::{
+    checked_general_index_to_subtree, checked_subtree_index_to_general, is_in_subtree,
+};
+use crate::types::VariableList;
+use ethereum_types::U256;
+use typenum::U8;
+
+// S's merkle tree
+//
+//         root(0)
+//        /       \
+//      a(1)      b(2)
+//                 /   \
+//           data(5) len(6)
+//           /      \
+//      i(11)        i(12)
+//      /   \       /     \
+//  b0(23) b2(24) b4(26) b6(27)
+#[derive(Debug, Default)]
+pub struct S {
+    pub a: U256,
+    pub b: VariableList<u128, U8>,
+}
+
+impl MerkleTreeOverlay for S {
+    fn height() -> u64 {
+        1
+    }
+
+    fn max_depth() -> u64 {
+        Self::height() + VariableList::<u128, U8>::max_depth()
+    }
+
+    fn min_repr_size() -> u64 {
+        32
+    }
+
+    fn is_list() -> bool {
+        false
+    }
+
+    fn get_node(path: Vec<PathElement>) -> Result<Node, Error> {
+        let (index, rest) = match path.first() {
+            Some(PathElement::Ident(ident)) if ident == "a" => (1, &path[1..]),
+            Some(PathElement::Ident(ident)) if ident == "b" => (2, &path[1..]),
+            Some(p) => return Err(Error::InvalidPath(p.clone())),
+            None => return Err(Error::EmptyPath()),
+        };
+
+        if rest.is_empty() {
+            return Ok(Node {
+                ident: path[0].clone(),
+                index,
+                size: if index == 1 { 32 } else { 0 },
+                offset: 0,
+                height: if index == 1 { 0 } else { 3 },
+                is_list: index == 2,
+            });
+        }
+
+        let node = if index == 1 {
+            U256::get_node(rest.to_vec())?
+        } else {
+            VariableList::<u128, U8>::get_node(rest.to_vec())?
+        };
+
+        let general =
+            checked_subtree_index_to_general(index, node.index).ok_or(Error::IndexOverflow())?;
+        Ok(replace_index(node, general))
+    }
+
+    fn get_path(index: NodeIndex) -> Option<Vec<PathElement>> {
+        if index == 0 {
+            return Some(vec![]);
+        }
+
+        let (ident, path) = if is_in_subtree(1, index) {
+            let index = checked_general_index_to_subtree(1, index)?;
+            ("a", U256::get_path(index)?)
+        } else {
+            let index = checked_general_index_to_subtree(2, index)?;
+            ("b", VariableList::<u128, U8>::get_path(index)?)
+        };
+
+        let mut ret = vec![PathElement::from_ident_str(ident)];
+        ret.extend(path);
+        Some(ret)
+    }
+}
+
+impl MerkleTreeValue for S {
+    fn merkleize<H: Hasher, D: NodeStore>(
+        &self,
+        db: &mut Backend<H, D>,
+        root: NodeIndex,
+    ) -> Result<(), Error> {
+        let general =
+            |index| checked_subtree_index_to_general(root, index).ok_or(Error::IndexOverflow());
+
+        self.a.merkleize(db, general(1)?)?;
+        self.b.merkleize(db, general(2)?)?;
+        db.hash_subtree(root, 1, 2)?;
+        Ok(())
+    }
+}
+
+/// Returns `S { a: 1, b }`.
+pub fn s(b: Vec<u128>) -> S {
+    S {
+        a: U256::from(1),
+        b: VariableList::new(b).unwrap(),
+    }
+}
+
+/// Returns the full proof of `S { a: 1, b: [1, 2, 3] }`.
+pub fn s_proof() -> Proof<S, Sha256Hasher> {
+    Proof::from_value(&s(vec![1, 2, 3]))
+}
+
+/// Returns the path to `S.b[i]`.
+pub fn b(i: u64) -> Vec<PathElement> {
+    vec![PathElement::from_ident_str("b"), PathElement::Index(i)]
+}
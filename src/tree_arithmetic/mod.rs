@@ -1,32 +1,45 @@
 pub mod zeroed;
 
+use crate::NodeIndex;
+use std::collections::HashSet;
+
 /// Return a node's family. `index` is zero indexed.
-pub fn expand_tree_index(index: u64) -> (u64, u64, u64) {
-    let left = index - (index & 1) + (index == 1) as u64;
-    let right = index + (index & 1 ^ 1) + (index == 1) as u64;
-    let parent = left / 2 + (index == 1) as u64;
+pub fn expand_tree_index(index: NodeIndex) -> (NodeIndex, NodeIndex, NodeIndex) {
+    let left = index - (index & 1) + (index == 1) as NodeIndex;
+    let right = index + (index & 1 ^ 1) + (index == 1) as NodeIndex;
+    let parent = left / 2 + (index == 1) as NodeIndex;
 
     (left, right, parent)
 }
 
 /// Return the index of a node's sibling. `index` is zero indexed.
-pub const fn sibling_index(index: u64) -> u64 {
-    index - (index & 1) + (index & 1 ^ 1) + (index == 1) as u64
+pub const fn sibling_index(index: NodeIndex) -> NodeIndex {
+    index - (index & 1) + (index & 1 ^ 1) + (index == 1) as NodeIndex
 }
 
 /// Return the first leaf of a tree described by `root` and `depth`.
-pub const fn left_most_leaf(root: u64, depth: u64) -> u64 {
+///
+/// The leaf must fit in a `NodeIndex`, see `checked_left_most_leaf`.
+const fn left_most_leaf(root: NodeIndex, depth: u64) -> NodeIndex {
     root * (1 << depth)
 }
 
 /// Return the last leaf of a tree described by `root` and `depth`.
-pub const fn right_most_leaf(root: u64, depth: u64) -> u64 {
+///
+/// The leaf must fit in a `NodeIndex`.
+const fn right_most_leaf(root: NodeIndex, depth: u64) -> NodeIndex {
     let pow = 1 << depth;
-    root * pow + pow - 1
+    root * pow + (pow - 1)
 }
 
 /// Determine if `index` is in the subtree rooted at `root`.
-pub const fn is_in_subtree(root: u64, index: u64) -> bool {
+pub const fn is_in_subtree(root: NodeIndex, index: NodeIndex) -> bool {
+    // The leaves of `root` at the depth of `index` only fit in a `NodeIndex` if `index` is not
+    // above `root`.
+    if log_base_two(last_power_of_two(index)) < log_base_two(last_power_of_two(root)) {
+        return false;
+    }
+
     let diff = relative_depth(root, index);
     let left_most = left_most_leaf(root, diff);
     let right_most = right_most_leaf(root, diff);
@@ -35,7 +48,7 @@ pub const fn is_in_subtree(root: u64, index: u64) -> bool {
 }
 
 /// Return the depth between two general indicies.
-pub const fn relative_depth(a: u64, b: u64) -> u64 {
+pub const fn relative_depth(a: NodeIndex, b: NodeIndex) -> u64 {
     let a = log_base_two(last_power_of_two(a));
     let b = log_base_two(last_power_of_two(b));
 
@@ -44,8 +57,8 @@ pub const fn relative_depth(a: u64, b: u64) -> u64 {
 
 /// Return the next power of two for `n` using bit twiddling.
 /// https://graphics.stanford.edu/~seander/bithacks.html#RoundUpPowerOf2
-pub const fn next_power_of_two(n: u64) -> u64 {
-    let mut ret: u128 = (n - 1) as u128;
+pub const fn next_power_of_two(n: NodeIndex) -> NodeIndex {
+    let mut ret = n - 1;
 
     ret = ret | ret >> 1;
     ret = ret | ret >> 2;
@@ -53,13 +66,14 @@ pub const fn next_power_of_two(n: u64) -> u64 {
     ret = ret | ret >> 8;
     ret = ret | ret >> 16;
     ret = ret | ret >> 32;
+    ret = ret | ret >> 64;
 
-    (ret + 1) as u64
+    ret + 1
 }
 
 /// Return the last power of two for `n` using bit twiddling.
-pub const fn last_power_of_two(n: u64) -> u64 {
-    let mut ret: u128 = n as u128;
+pub const fn last_power_of_two(n: NodeIndex) -> NodeIndex {
+    let mut ret = n;
 
     ret = ret | ret >> 1;
     ret = ret | ret >> 2;
@@ -67,37 +81,93 @@ pub const fn last_power_of_two(n: u64) -> u64 {
     ret = ret | ret >> 8;
     ret = ret | ret >> 16;
     ret = ret | ret >> 32;
+    ret = ret | ret >> 64;
 
-    ((ret + 1) >> 1) as u64
+    ret - (ret >> 1)
 }
 
 /// Return the subtree root for `index` assuming the tree is `depth` deep.
-pub const fn root_from_depth(index: u64, depth: u64) -> u64 {
+pub const fn root_from_depth(index: NodeIndex, depth: u64) -> NodeIndex {
     index / (1 << depth)
 }
 
-/// Return the log of `n` using the De Bruijn method.
-/// https://graphics.stanford.edu/~seander/bithacks.html#IntegerLogDeBruijn
-pub const fn log_base_two(n: u64) -> u64 {
-    const DE_BRUIJN_BIT_POSITION: &'static [u64] = &[
-        63, 0, 58, 1, 59, 47, 53, 2, 60, 39, 48, 27, 54, 33, 42, 3, 61, 51, 37, 40, 49, 18, 28, 20,
-        55, 30, 34, 11, 43, 14, 22, 4, 62, 57, 46, 52, 38, 26, 32, 41, 50, 36, 17, 19, 29, 10, 13,
-        21, 56, 45, 25, 31, 35, 16, 9, 12, 44, 24, 15, 8, 23, 7, 6, 5,
-    ];
+/// Return the log of `n`, where `n` is a power of two.
+pub const fn log_base_two(n: NodeIndex) -> u64 {
+    127 - n.leading_zeros() as u64
+}
 
-    DE_BRUIJN_BIT_POSITION[(n.wrapping_mul(0x07EDD5E59A4E28C2) >> 58) as usize]
+/// Translate the subtree index `index` rooted at `root` into a general index, returning `None`
+/// if the general index does not fit in a `NodeIndex`.
+pub fn checked_subtree_index_to_general(root: NodeIndex, index: NodeIndex) -> Option<NodeIndex> {
+    let pow = last_power_of_two(index);
+    root.checked_mul(pow)?.checked_add(index - pow)
 }
 
-/// Translate the subtree index `index` rooted at `root` into a general index.
-pub const fn subtree_index_to_general(root: u64, index: u64) -> u64 {
-    (root * index) - (root - 1) * (index - last_power_of_two(index))
+/// Return the first leaf of a tree described by `root` and `depth`, returning `None` if the leaf
+/// does not fit in a `NodeIndex`.
+pub fn checked_left_most_leaf(root: NodeIndex, depth: u64) -> Option<NodeIndex> {
+    if depth >= 128 {
+        return None;
+    }
+
+    root.checked_mul(1 << depth)
 }
 
-/// Translate the general index `index` into a subtree index rooted at `root`.
-pub const fn general_index_to_subtree(root: u64, index: u64) -> u64 {
-    let depth_diff = log_base_two(last_power_of_two(index)) - log_base_two(last_power_of_two(root));
+/// Translate the general index `index` into a subtree index rooted at `root`, returning `None`
+/// if `index` is not in the subtree.
+pub fn checked_general_index_to_subtree(root: NodeIndex, index: NodeIndex) -> Option<NodeIndex> {
+    if !is_in_subtree(root, index) {
+        return None;
+    }
+
+    // The leaves of `root` at the depth of `index` start at `root << depth`, which is at most
+    // `index`, so the subtraction does not overflow.
+    Some(index - ((root - 1) << relative_depth(root, index)))
+}
+
+/// Return the indices of the sibling nodes along the path from `index` to the root.
+pub fn get_branch_indices(index: NodeIndex) -> Vec<NodeIndex> {
+    let mut ret = vec![sibling_index(index)];
+
+    while ret[ret.len() - 1] > 1 {
+        let parent = ret[ret.len() - 1] / 2;
+        ret.push(sibling_index(parent));
+    }
+
+    ret.pop();
+    ret
+}
+
+/// Return the indices of the nodes along the path from `index` to the root, excluding the root.
+pub fn get_path_indices(index: NodeIndex) -> Vec<NodeIndex> {
+    let mut ret = vec![index];
+
+    while ret[ret.len() - 1] > 1 {
+        let parent = ret[ret.len() - 1] / 2;
+        ret.push(parent);
+    }
 
-    (1 << depth_diff) + index - ((1 << depth_diff) * root)
+    ret.pop();
+    ret
+}
+
+/// Return the helper nodes needed to prove all `indices`, sorted in decreasing order.
+///
+/// Nodes which can be calculated from other helpers or from the leaves themselves are omitted.
+pub fn get_helper_indices(indices: &[NodeIndex]) -> Vec<NodeIndex> {
+    let mut helpers: Vec<NodeIndex> = vec![];
+    let mut paths: HashSet<NodeIndex> = HashSet::new();
+
+    for index in indices {
+        helpers.extend(get_branch_indices(*index));
+        paths.extend(get_path_indices(*index));
+    }
+
+    let mut ret: Vec<NodeIndex> = helpers.into_iter().filter(|h| !paths.contains(h)).collect();
+
+    ret.sort_by(|a, b| b.cmp(a));
+    ret.dedup();
+    ret
 }
 
 #[cfg(test)]
@@ -117,7 +187,7 @@ mod tests {
         assert_eq!(relative_depth(11, 30), 1);
         assert_eq!(relative_depth(28, 30), 0);
 
-        assert_eq!(relative_depth(1, 2_u64.pow(63)), 63);
+        assert_eq!(relative_depth(1, 2_u128.pow(63)), 63);
     }
 
     #[test]
@@ -174,13 +244,21 @@ mod tests {
         assert_eq!(last_power_of_two(2), 2);
         assert_eq!(last_power_of_two(9), 8);
         assert_eq!(last_power_of_two(1023), 512);
-        assert_eq!(last_power_of_two(2_u64.pow(63) + 1000), 2_u64.pow(63));
+        assert_eq!(last_power_of_two(2_u128.pow(63) + 1000), 2_u128.pow(63));
 
         assert_eq!(next_power_of_two(1), 1);
         assert_eq!(next_power_of_two(2), 2);
         assert_eq!(next_power_of_two(9), 16);
         assert_eq!(next_power_of_two(1023), 1024);
-        assert_eq!(next_power_of_two(2_u64.pow(62) + 1000), 2_u64.pow(63));
+        assert_eq!(next_power_of_two(2_u128.pow(62) + 1000), 2_u128.pow(63));
+    }
+
+    fn subtree_index_to_general(root: NodeIndex, index: NodeIndex) -> NodeIndex {
+        checked_subtree_index_to_general(root, index).unwrap()
+    }
+
+    fn general_index_to_subtree(root: NodeIndex, index: NodeIndex) -> NodeIndex {
+        checked_general_index_to_subtree(root, index).unwrap()
     }
 
     #[test]
@@ -237,53 +315,93 @@ mod tests {
 
     #[test]
     fn compute_log_base_two() {
-        assert_eq!(log_base_two(2_u64.pow(1)), 1);
-        assert_eq!(log_base_two(2_u64.pow(10)), 10);
-        assert_eq!(log_base_two(2_u64.pow(33)), 33);
-        assert_eq!(log_base_two(2_u64.pow(45)), 45);
-        assert_eq!(log_base_two(2_u64.pow(63)), 63);
+        assert_eq!(log_base_two(2_u128.pow(1)), 1);
+        assert_eq!(log_base_two(2_u128.pow(10)), 10);
+        assert_eq!(log_base_two(2_u128.pow(33)), 33);
+        assert_eq!(log_base_two(2_u128.pow(45)), 45);
+        assert_eq!(log_base_two(2_u128.pow(63)), 63);
     }
 
     #[test]
     fn compute_left_most_leaf() {
         assert_eq!(left_most_leaf(1, 1), 2);
-        assert_eq!(left_most_leaf(1, 9), 2_u64.pow(9));
-        assert_eq!(left_most_leaf(1, 50), 2_u64.pow(50));
+        assert_eq!(left_most_leaf(1, 9), 2_u128.pow(9));
+        assert_eq!(left_most_leaf(1, 50), 2_u128.pow(50));
 
-        assert_eq!(left_most_leaf(2, 1), 2 * 2_u64.pow(1));
-        assert_eq!(left_most_leaf(2, 4), 2 * 2_u64.pow(4));
-        assert_eq!(left_most_leaf(2, 5), 2 * 2_u64.pow(5));
+        assert_eq!(left_most_leaf(2, 1), 2 * 2_u128.pow(1));
+        assert_eq!(left_most_leaf(2, 4), 2 * 2_u128.pow(4));
+        assert_eq!(left_most_leaf(2, 5), 2 * 2_u128.pow(5));
 
-        assert_eq!(left_most_leaf(6, 1), 6 * 2_u64.pow(1));
-        assert_eq!(left_most_leaf(6, 2), 6 * 2_u64.pow(2));
-        assert_eq!(left_most_leaf(6, 11), 6 * 2_u64.pow(11));
+        assert_eq!(left_most_leaf(6, 1), 6 * 2_u128.pow(1));
+        assert_eq!(left_most_leaf(6, 2), 6 * 2_u128.pow(2));
+        assert_eq!(left_most_leaf(6, 11), 6 * 2_u128.pow(11));
 
-        assert_eq!(left_most_leaf(25, 1), 25 * 2_u64.pow(1));
+        assert_eq!(left_most_leaf(25, 1), 25 * 2_u128.pow(1));
     }
 
     #[test]
     fn compute_right_most_leaf() {
         assert_eq!(right_most_leaf(1, 1), 3);
-        assert_eq!(right_most_leaf(1, 9), 2_u64.pow(10) - 1);
-        assert_eq!(right_most_leaf(1, 50), 2_u64.pow(51) - 1);
+        assert_eq!(right_most_leaf(1, 9), 2_u128.pow(10) - 1);
+        assert_eq!(right_most_leaf(1, 50), 2_u128.pow(51) - 1);
 
-        assert_eq!(right_most_leaf(2, 1), 2 * 2_u64.pow(1) + 1);
-        assert_eq!(right_most_leaf(2, 4), 2 * 2_u64.pow(4) + 15);
-        assert_eq!(right_most_leaf(2, 5), 2 * 2_u64.pow(5) + 31);
+        assert_eq!(right_most_leaf(2, 1), 2 * 2_u128.pow(1) + 1);
+        assert_eq!(right_most_leaf(2, 4), 2 * 2_u128.pow(4) + 15);
+        assert_eq!(right_most_leaf(2, 5), 2 * 2_u128.pow(5) + 31);
 
-        assert_eq!(right_most_leaf(6, 1), 6 * 2_u64.pow(1) + 1);
-        assert_eq!(right_most_leaf(6, 2), 6 * 2_u64.pow(2) + 3);
-        assert_eq!(right_most_leaf(6, 11), 6 * 2_u64.pow(11) + 2047);
+        assert_eq!(right_most_leaf(6, 1), 6 * 2_u128.pow(1) + 1);
+        assert_eq!(right_most_leaf(6, 2), 6 * 2_u128.pow(2) + 3);
+        assert_eq!(right_most_leaf(6, 11), 6 * 2_u128.pow(11) + 2047);
 
-        assert_eq!(right_most_leaf(25, 1), 25 * 2_u64.pow(1) + 1);
+        assert_eq!(right_most_leaf(25, 1), 25 * 2_u128.pow(1) + 1);
     }
 
     #[test]
     fn determine_if_index_is_in_subtree() {
-        assert_eq!(is_in_subtree(1, 3), true);
-        assert_eq!(is_in_subtree(1, 100), true);
-        assert_eq!(is_in_subtree(2, 10), true);
-        assert_eq!(is_in_subtree(2, 6), false);
-        assert_eq!(is_in_subtree(2, 15), false);
+        assert!(is_in_subtree(1, 3));
+        assert!(is_in_subtree(1, 100));
+        assert!(is_in_subtree(2, 10));
+        assert!(!is_in_subtree(2, 6));
+        assert!(!is_in_subtree(2, 15));
+    }
+
+    #[test]
+    fn compute_helper_indices() {
+        assert_eq!(get_branch_indices(9), vec![8, 5, 3]);
+        assert_eq!(get_path_indices(9), vec![9, 4, 2]);
+
+        assert_eq!(get_helper_indices(&[1]), vec![]);
+        assert_eq!(get_helper_indices(&[9]), vec![8, 5, 3]);
+        assert_eq!(get_helper_indices(&[8, 9]), vec![5, 3]);
+        assert_eq!(get_helper_indices(&[9, 14]), vec![15, 8, 6, 5]);
+        assert_eq!(get_helper_indices(&[2, 9]), vec![8, 5, 3]);
+    }
+
+    #[test]
+    fn checked_index_overflow() {
+        assert_eq!(checked_left_most_leaf(1, 50), Some(2_u128.pow(50)));
+        assert_eq!(checked_left_most_leaf(1, 127), Some(2_u128.pow(127)));
+        assert_eq!(checked_left_most_leaf(2, 127), None);
+        assert_eq!(checked_left_most_leaf(1, 128), None);
+
+        assert_eq!(checked_subtree_index_to_general(26, 12), Some(212));
+        assert_eq!(
+            checked_subtree_index_to_general(2_u128.pow(64), 2_u128.pow(63)),
+            Some(2_u128.pow(127))
+        );
+        assert_eq!(
+            checked_subtree_index_to_general(2_u128.pow(64), 2_u128.pow(64)),
+            None
+        );
+
+        assert_eq!(checked_general_index_to_subtree(2, 6), None);
+        assert_eq!(checked_general_index_to_subtree(6, 2), None);
+        assert_eq!(
+            checked_general_index_to_subtree(3, NodeIndex::MAX - 1),
+            Some(2_u128.pow(127) - 2)
+        );
+
+        assert_eq!(log_base_two(2_u128.pow(127)), 127);
+        assert_eq!(last_power_of_two(NodeIndex::MAX), 2_u128.pow(127));
     }
 }
@@ -1,38 +1,80 @@
 use crate::tree_arithmetic;
+use crate::NodeIndex;
 
-pub const fn subtree_index_to_general(root: u64, index: u64) -> u64 {
-    tree_arithmetic::subtree_index_to_general(root + 1, index + 1) - 1
+/// Converts the zero-indexed `index` into a 1-indexed general index.
+///
+/// # Panics
+/// Panics if `index` is `NodeIndex::MAX`, whose general index does not fit in a `NodeIndex`.
+/// Proofs which contain it are rejected before any of these functions are called on it.
+const fn general(index: NodeIndex) -> NodeIndex {
+    match index.checked_add(1) {
+        Some(index) => index,
+        None => panic!("node index has no general index"),
+    }
 }
 
-pub const fn general_index_to_subtree(root: u64, index: u64) -> u64 {
-    tree_arithmetic::general_index_to_subtree(root + 1, index + 1) - 1
+pub fn checked_subtree_index_to_general(root: NodeIndex, index: NodeIndex) -> Option<NodeIndex> {
+    tree_arithmetic::checked_subtree_index_to_general(root.checked_add(1)?, index.checked_add(1)?)
+        .map(|i| i - 1)
 }
 
-pub fn expand_tree_index(index: u64) -> (u64, u64, u64) {
-    let (left, right, parent) = tree_arithmetic::expand_tree_index(index + 1);
+pub fn checked_general_index_to_subtree(root: NodeIndex, index: NodeIndex) -> Option<NodeIndex> {
+    tree_arithmetic::checked_general_index_to_subtree(root.checked_add(1)?, index.checked_add(1)?)
+        .map(|i| i - 1)
+}
+
+pub fn expand_tree_index(index: NodeIndex) -> (NodeIndex, NodeIndex, NodeIndex) {
+    let (left, right, parent) = tree_arithmetic::expand_tree_index(general(index));
     (left - 1, right - 1, parent - 1)
 }
 
-pub const fn sibling_index(index: u64) -> u64 {
-    tree_arithmetic::sibling_index(index + 1) - 1
+pub const fn sibling_index(index: NodeIndex) -> NodeIndex {
+    tree_arithmetic::sibling_index(general(index)) - 1
 }
 
-pub const fn left_most_leaf(root: u64, depth: u64) -> u64 {
-    tree_arithmetic::left_most_leaf(root + 1, depth) - 1
+pub fn checked_left_most_leaf(root: NodeIndex, depth: u64) -> Option<NodeIndex> {
+    tree_arithmetic::checked_left_most_leaf(root.checked_add(1)?, depth).map(|i| i - 1)
 }
 
-pub const fn right_most_leaf(root: u64, depth: u64) -> u64 {
-    tree_arithmetic::right_most_leaf(root + 1, depth) - 1
+pub const fn is_in_subtree(root: NodeIndex, index: NodeIndex) -> bool {
+    tree_arithmetic::is_in_subtree(general(root), general(index))
 }
 
-pub const fn is_in_subtree(root: u64, index: u64) -> bool {
-    tree_arithmetic::is_in_subtree(root + 1, index + 1)
+pub const fn root_from_depth(index: NodeIndex, depth: u64) -> NodeIndex {
+    tree_arithmetic::root_from_depth(general(index), depth) - 1
 }
 
-pub const fn root_from_depth(index: u64, depth: u64) -> u64 {
-    tree_arithmetic::root_from_depth(index + 1, depth) - 1
+pub const fn relative_depth(a: NodeIndex, b: NodeIndex) -> u64 {
+    tree_arithmetic::relative_depth(general(a), general(b))
 }
 
-pub const fn relative_depth(a: u64, b: u64) -> u64 {
-    tree_arithmetic::relative_depth(a + 1, b + 1)
+/// Returns the depth of the node at `index`, where the root is at depth 0.
+pub fn index_depth(index: NodeIndex) -> u64 {
+    match index.checked_add(1) {
+        Some(i) => 127 - i.leading_zeros() as u64,
+        None => 128,
+    }
+}
+
+pub fn get_branch_indices(index: NodeIndex) -> Vec<NodeIndex> {
+    tree_arithmetic::get_branch_indices(general(index))
+        .into_iter()
+        .map(|i| i - 1)
+        .collect()
+}
+
+pub fn get_path_indices(index: NodeIndex) -> Vec<NodeIndex> {
+    tree_arithmetic::get_path_indices(general(index))
+        .into_iter()
+        .map(|i| i - 1)
+        .collect()
+}
+
+pub fn get_helper_indices(indices: &[NodeIndex]) -> Vec<NodeIndex> {
+    let indices: Vec<NodeIndex> = indices.iter().map(|i| general(*i)).collect();
+
+    tree_arithmetic::get_helper_indices(&indices)
+        .into_iter()
+        .map(|i| i - 1)
+        .collect()
 }
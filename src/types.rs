@@ -1,14 +1,83 @@
+use crate::error::{Error, Result};
 use std::marker::PhantomData;
+use std::ops::Deref;
 use typenum::Unsigned;
 
 #[derive(Debug, Default)]
 pub struct VariableList<T, N: Unsigned> {
-    _phantom_type: PhantomData<T>,
+    items: Vec<T>,
     _phantom_count: PhantomData<N>,
 }
 
-#[derive(Debug, Default)]
+impl<T, N: Unsigned> VariableList<T, N> {
+    /// Creates a list from `items`, returning an error if there are more than `N` items.
+    pub fn new(items: Vec<T>) -> Result<Self> {
+        if items.len() > N::to_usize() {
+            return Err(Error::IndexOutOfBounds(N::to_u64()));
+        }
+
+        Ok(Self {
+            items,
+            _phantom_count: PhantomData,
+        })
+    }
+}
+
+impl<T, N: Unsigned> Deref for VariableList<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+#[derive(Debug)]
 pub struct FixedVector<T, N: Unsigned> {
-    _phantom_type: PhantomData<T>,
+    items: Vec<T>,
     _phantom_count: PhantomData<N>,
 }
+
+impl<T, N: Unsigned> FixedVector<T, N> {
+    /// Creates a vector from `items`, returning an error unless there are exactly `N` items.
+    pub fn new(items: Vec<T>) -> Result<Self> {
+        if items.len() != N::to_usize() {
+            return Err(Error::InvalidLength(N::to_usize(), items.len()));
+        }
+
+        Ok(Self {
+            items,
+            _phantom_count: PhantomData,
+        })
+    }
+}
+
+/// Returns a vector of `N` default items.
+impl<T: Default, N: Unsigned> Default for FixedVector<T, N> {
+    fn default() -> Self {
+        Self {
+            items: (0..N::to_usize()).map(|_| T::default()).collect(),
+            _phantom_count: PhantomData,
+        }
+    }
+}
+
+impl<T, N: Unsigned> Deref for FixedVector<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use typenum::U4;
+
+    #[test]
+    fn default_fixed_vector() {
+        let vector = FixedVector::<u64, U4>::default();
+        assert_eq!(&vector[..], &[0; 4]);
+        assert!(VariableList::<u64, U4>::default().is_empty());
+    }
+}
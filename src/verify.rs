@@ -0,0 +1,268 @@
+use super::NodeIndex;
+use crate::chunk::Chunk;
+use crate::error::{Error, Result};
+use crate::generalized_index::GeneralizedIndex;
+use crate::hasher::{Hasher, Sha256Hasher};
+use crate::tree_arithmetic::zeroed::{expand_tree_index, get_helper_indices, sibling_index};
+
+/// The depth of the deepest tree whose nodes can be addressed by a `NodeIndex`.
+const MAX_DEPTH: u64 = 8 * std::mem::size_of::<NodeIndex>() as u64;
+
+/// Verifies that `leaf` is located at position `index` of a tree with `depth` levels below
+/// `root`, using the sibling chunks in `branch` ordered from the bottom of the tree to the top.
+/// Nodes are hashed with SHA-256.
+///
+/// `index` is the position of the leaf among the `2^depth` leaves of the tree, counting from
+/// zero, as in the specification.
+pub fn is_valid_merkle_branch(
+    leaf: &Chunk,
+    branch: &[Chunk],
+    depth: u64,
+    index: NodeIndex,
+    root: &Chunk,
+) -> Result<()> {
+    is_valid_merkle_branch_with_hasher::<Sha256Hasher>(leaf, branch, depth, index, root)
+}
+
+/// Same as `is_valid_merkle_branch`, but nodes are hashed with `H`.
+///
+/// Returns `Error::InvalidDepth` if `depth` is greater than the depth of a tree which can be
+/// addressed by a `NodeIndex`.
+pub fn is_valid_merkle_branch_with_hasher<H: Hasher>(
+    leaf: &Chunk,
+    branch: &[Chunk],
+    depth: u64,
+    index: NodeIndex,
+    root: &Chunk,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(Error::InvalidDepth(depth));
+    }
+
+    if (branch.len() as u64) < depth {
+        return Err(Error::ChunkCountMismatch(depth as usize, branch.len()));
+    }
+
+    let mut value = *leaf;
+
+    for (i, sibling) in branch.iter().take(depth as usize).enumerate() {
+        if (index >> i) & 1 == 1 {
+            value = H::hash_children(sibling, &value);
+        } else {
+            value = H::hash_children(&value, sibling);
+        }
+    }
+
+    if value == *root {
+        Ok(())
+    } else {
+        Err(Error::RootMismatch(*root, value))
+    }
+}
+
+/// Verifies that each of `leaves` is located at the corresponding generalized index in
+/// `indices` below `root`. Nodes are hashed with SHA-256.
+///
+/// `proof` must contain the chunks for the helper nodes of `indices`, in the decreasing order
+/// returned by `get_helper_indices`.
+pub fn verify_merkle_multiproof(
+    leaves: &[Chunk],
+    proof: &[Chunk],
+    indices: &[GeneralizedIndex],
+    root: &Chunk,
+) -> Result<()> {
+    verify_merkle_multiproof_with_hasher::<Sha256Hasher>(leaves, proof, indices, root)
+}
+
+/// Same as `verify_merkle_multiproof`, but nodes are hashed with `H`.
+pub fn verify_merkle_multiproof_with_hasher<H: Hasher>(
+    leaves: &[Chunk],
+    proof: &[Chunk],
+    indices: &[GeneralizedIndex],
+    root: &Chunk,
+) -> Result<()> {
+    let indices: Vec<NodeIndex> = indices.iter().map(|i| i.to_node_index()).collect();
+    let calculated = calculate_multi_merkle_root::<H>(leaves, proof, &indices)?;
+
+    if calculated == *root {
+        Ok(())
+    } else {
+        Err(Error::RootMismatch(*root, calculated))
+    }
+}
+
+/// Calculates the root of the tree described by `leaves` at `indices` and the helper chunks in
+/// `proof`.
+fn calculate_multi_merkle_root<H: Hasher>(
+    leaves: &[Chunk],
+    proof: &[Chunk],
+    indices: &[NodeIndex],
+) -> Result<Chunk> {
+    if leaves.len() != indices.len() {
+        return Err(Error::ChunkCountMismatch(indices.len(), leaves.len()));
+    }
+
+    let helpers = get_helper_indices(indices);
+
+    if proof.len() != helpers.len() {
+        return Err(Error::ChunkCountMismatch(helpers.len(), proof.len()));
+    }
+
+    let mut nodes: Vec<(NodeIndex, Chunk)> = indices
+        .iter()
+        .cloned()
+        .zip(leaves.iter().cloned())
+        .chain(helpers.into_iter().zip(proof.iter().cloned()))
+        .collect();
+
+    // Sorting in increasing order means the deepest node is always at the end of `nodes`.
+    nodes.sort_by_key(|n| n.0);
+    nodes.dedup_by(|a, b| a.0 == b.0);
+
+    while let Some((index, chunk)) = nodes.pop() {
+        if index == 0 {
+            return Ok(chunk);
+        }
+
+        let (left, _, parent) = expand_tree_index(index);
+
+        // Every node deeper than `index` has already been consumed, so if the sibling is
+        // available it must be the next node.
+        if index == left || nodes.last().map(|n| n.0) != Some(left) {
+            return Err(Error::ChunkNotLoaded(sibling_index(index)));
+        }
+
+        let (_, sibling) = nodes.pop().expect("sibling to be loaded");
+        let hash = H::hash_children(&sibling, &chunk);
+
+        if let Err(position) = nodes.binary_search_by(|n| n.0.cmp(&parent)) {
+            nodes.insert(position, (parent, hash));
+        }
+    }
+
+    Err(Error::ChunkNotLoaded(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::hash_children;
+    use crate::hasher::XorHasher;
+    use crate::BYTES_PER_CHUNK;
+
+    //         0
+    //       /   \
+    //      1     2
+    //     / \   / \
+    //    3   4 5   6
+    fn build_tree() -> Vec<Chunk> {
+        let mut tree = vec![Chunk::default(); 7];
+
+        for (i, node) in tree.iter_mut().enumerate().skip(3) {
+            *node = Chunk([i as u8; BYTES_PER_CHUNK]);
+        }
+
+        tree[2] = hash_children(&tree[5], &tree[6]);
+        tree[1] = hash_children(&tree[3], &tree[4]);
+        tree[0] = hash_children(&tree[1], &tree[2]);
+
+        tree
+    }
+
+    fn generalized(indices: &[NodeIndex]) -> Vec<GeneralizedIndex> {
+        indices
+            .iter()
+            .map(|i| GeneralizedIndex::from_node_index(*i).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn verify_single_branch() {
+        let tree = build_tree();
+
+        assert_eq!(
+            is_valid_merkle_branch(&tree[5], &[tree[6], tree[1]], 2, 2, &tree[0]),
+            Ok(())
+        );
+
+        assert_eq!(
+            is_valid_merkle_branch(&tree[4], &[tree[3], tree[2]], 2, 1, &tree[0]),
+            Ok(())
+        );
+
+        assert_eq!(
+            is_valid_merkle_branch(&tree[4], &[tree[3], tree[2]], 2, 0, &tree[0]),
+            Err(Error::RootMismatch(
+                tree[0],
+                hash_children(&hash_children(&tree[4], &tree[3]), &tree[2])
+            ))
+        );
+
+        assert_eq!(
+            is_valid_merkle_branch(&tree[4], &[tree[3]], 2, 1, &tree[0]),
+            Err(Error::ChunkCountMismatch(2, 1))
+        );
+
+        assert_eq!(
+            is_valid_merkle_branch(&tree[4], &[tree[3]; 129], 129, 1, &tree[0]),
+            Err(Error::InvalidDepth(129))
+        );
+
+        // Any hasher can be used
+        let leaf = Chunk([1; BYTES_PER_CHUNK]);
+        let sibling = Chunk([2; BYTES_PER_CHUNK]);
+        assert_eq!(
+            is_valid_merkle_branch_with_hasher::<XorHasher>(
+                &leaf,
+                &[sibling],
+                1,
+                0,
+                &Chunk([3; BYTES_PER_CHUNK])
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn verify_multiproof() {
+        let tree = build_tree();
+
+        assert_eq!(
+            verify_merkle_multiproof(
+                &[tree[3], tree[6]],
+                &[tree[5], tree[4]],
+                &generalized(&[3, 6]),
+                &tree[0]
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_merkle_multiproof(&[tree[2]], &[tree[1]], &generalized(&[2]), &tree[0]),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_merkle_multiproof(&[tree[0]], &[], &generalized(&[0]), &tree[0]),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_merkle_multiproof(
+                &[tree[4], tree[6]],
+                &[tree[5], tree[4]],
+                &generalized(&[3, 6]),
+                &tree[0]
+            ),
+            Err(Error::RootMismatch(
+                tree[0],
+                hash_children(&hash_children(&tree[4], &tree[4]), &tree[2])
+            ))
+        );
+
+        assert_eq!(
+            verify_merkle_multiproof(&[tree[3]], &[tree[4]], &generalized(&[3]), &tree[0]),
+            Err(Error::ChunkCountMismatch(2, 1))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+#![cfg(feature = "cli")]
+
+use proof::{hash_children, Chunk, SerializedProof, BYTES_PER_CHUNK};
+use ssz::{Decode, Encode};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the `proof` binary with `args`, writing `input` to its stdin.
+fn proof(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_proof"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The binary may exit before reading its input, e.g. on bad arguments
+    if let Err(e) = child.stdin.take().unwrap().write_all(input) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+
+    child.wait_with_output().unwrap()
+}
+
+fn hex(chunk: &Chunk) -> String {
+    serde_json::to_value(chunk)
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn checkpoint_proofs() {
+    // Checkpoint { epoch: 3, root: 0x11.. }
+    let mut epoch = Chunk::default();
+    epoch[0] = 3;
+    let root = Chunk([0x11; BYTES_PER_CHUNK]);
+    let hash = hash_children(&epoch, &root);
+
+    let leaves = SerializedProof::from_nodes(vec![(1, epoch), (2, root)]);
+    let json = serde_json::to_vec(&leaves).unwrap();
+
+    // Verify against the root, with either encoding of the input
+    for input in &[json.clone(), leaves.as_ssz_bytes()] {
+        let output = proof(
+            &["verify", "--type", "Checkpoint", "--root", &hex(&hash)],
+            input,
+        );
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"ok\n");
+    }
+
+    let output = proof(
+        &["verify", "--type", "Checkpoint", "--root", &hex(&epoch)],
+        &json,
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("proof: verification failed"));
+
+    // Filling adds the root and keeps the encoding of the input
+    let output = proof(&["fill", "--type", "Checkpoint"], &leaves.as_ssz_bytes());
+    assert!(output.status.success());
+    assert_eq!(
+        SerializedProof::from_ssz_bytes(&output.stdout).unwrap(),
+        SerializedProof::from_nodes(vec![(0, hash), (1, epoch), (2, root)])
+    );
+
+    let output = proof(
+        &[
+            "extract",
+            "--type",
+            "Checkpoint",
+            "--path",
+            "root",
+            "--output",
+            "ssz",
+        ],
+        &json,
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        SerializedProof::from_ssz_bytes(&output.stdout).unwrap(),
+        SerializedProof::from_nodes(vec![(1, epoch), (2, root)])
+    );
+
+    let output = proof(&["show", "--type", "Checkpoint"], &json);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "     1   1 {} epoch\n     2   1 {} root\n",
+            hex(&epoch),
+            hex(&root)
+        )
+    );
+}
+
+#[test]
+fn reject_bad_arguments() {
+    let empty = SerializedProof::from_nodes(vec![]).as_ssz_bytes();
+
+    let output = proof(&["types"], &[]);
+    assert_eq!(
+        output.stdout,
+        b"BeaconBlockHeader\nCheckpoint\nEth1Data\n".to_vec()
+    );
+
+    for args in &[
+        &["show"][..],
+        &["show", "--type", "Unknown"],
+        &["show", "--type", "Checkpoint", "--format", "yaml"],
+        &["verify", "--type", "Checkpoint"],
+        &["frobnicate", "--type", "Checkpoint"],
+    ] {
+        let output = proof(args, &empty);
+        assert!(!output.status.success(), "{:?}", args);
+        assert!(!output.stderr.is_empty());
+    }
+}
+
+#[test]
+fn reject_crafted_proofs() {
+    let zero = hex(&Chunk::default());
+    let json = |indices: &str, chunks: usize| {
+        format!(
+            r#"{{"indices":[{}],"chunks":[{}]}}"#,
+            indices,
+            vec![format!(r#""{}""#, zero); chunks].join(",")
+        )
+        .into_bytes()
+    };
+
+    let mut wide = vec![
+        12, 0, 0, 0, 20, 0, 0, 0, 52, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    wide.extend_from_slice(&[0; BYTES_PER_CHUNK]);
+
+    for input in &[
+        json(&format!(r#""{}""#, u128::MAX), 1),
+        json(r#""1","1""#, 2),
+        json(r#""5""#, 1),
+        json(r#""1""#, 0),
+        json(r#""1e40""#, 1),
+        vec![8, 0, 0, 0, 0xff, 0xff, 0xff, 0xff],
+        wide,
+    ] {
+        for args in &[
+            &["verify", "--type", "Checkpoint", "--root", &zero][..],
+            &["fill", "--type", "Checkpoint"],
+            &["extract", "--type", "Checkpoint", "--path", "root"],
+            &["show", "--type", "Checkpoint"],
+        ] {
+            let output = proof(args, input);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            assert_eq!(output.status.code(), Some(1), "{:?}: {}", args, stderr);
+            assert!(
+                stderr.starts_with("proof: invalid"),
+                "{:?}: {}",
+                args,
+                stderr
+            );
+            assert!(!stderr.contains("panicked"));
+        }
+    }
+}
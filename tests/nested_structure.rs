@@ -1,9 +1,9 @@
 use ethereum_types::U256;
 use proof::impls::replace_index;
 use proof::node::Node;
-use proof::tree_arithmetic::zeroed::subtree_index_to_general;
+use proof::tree_arithmetic::zeroed::checked_subtree_index_to_general;
 use proof::types::VariableList;
-use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
+use proof::{hash_children, Chunk, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
 use typenum::U8;
 
 // S's merkle tree
@@ -17,6 +17,7 @@ use typenum::U8;
 //      i(11)        i(12)
 //      /   \       /     \
 //  b0(23) b2(24) b4(26) b6(27)
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct S {
     a: U256,
@@ -28,6 +29,10 @@ impl MerkleTreeOverlay for S {
         1
     }
 
+    fn max_depth() -> u64 {
+        Self::height() + VariableList::<u128, U8>::max_depth()
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -51,7 +56,8 @@ impl MerkleTreeOverlay for S {
                 match U256::get_node(path[1..].to_vec()) {
                     Ok(n) => Ok(replace_index(
                         n.clone(),
-                        subtree_index_to_general(1, n.index),
+                        checked_subtree_index_to_general(1, n.index)
+                            .ok_or(Error::IndexOverflow())?,
                     )),
                     e => e,
                 }
@@ -70,7 +76,8 @@ impl MerkleTreeOverlay for S {
                 match VariableList::<u128, U8>::get_node(path[1..].to_vec()) {
                     Ok(n) => Ok(replace_index(
                         n.clone(),
-                        subtree_index_to_general(2, n.index),
+                        checked_subtree_index_to_general(2, n.index)
+                            .ok_or(Error::IndexOverflow())?,
                     )),
                     e => e,
                 }
@@ -92,9 +99,9 @@ fn roundtrip_partial() {
     arr[63] = 3;
     arr[127] = 3; // length
 
-    let twelve: &[u8] = &hash_children(&arr[64..96], &arr[64..96]);
+    let twelve = hash_children(&Chunk::default(), &Chunk::default());
 
-    arr[64..96].copy_from_slice(twelve);
+    arr[64..96].copy_from_slice(twelve.as_ref());
 
     let sp = SerializedProof {
         indices: vec![23, 24, 12, 6, 1],
@@ -214,19 +221,19 @@ fn get_and_set_by_path() {
 
 #[test]
 fn readme_test() {
-    let one = vec![0u8; 32];
-    let six = vec![0u8; 32];
-    let twelve = hash_children(&[0u8; 32], &[0u8; 32]);
-    let twenty_three = vec![1u8; 32];
-    let twenty_four = vec![2u8; 32];
-
-    let serialized_proof = SerializedProof {
-        indices: vec![1, 6, 12, 23, 24],
-        chunks: vec![one, six, twelve, twenty_three, twenty_four]
-            .into_iter()
-            .flatten()
-            .collect(),
-    };
+    let one = Chunk::default();
+    let six = Chunk::default();
+    let twelve = hash_children(&Chunk::default(), &Chunk::default());
+    let twenty_three = Chunk([1; 32]);
+    let twenty_four = Chunk([2; 32]);
+
+    let serialized_proof = SerializedProof::from_nodes(vec![
+        (1, one),
+        (6, six),
+        (12, twelve),
+        (23, twenty_three),
+        (24, twenty_four),
+    ]);
     let mut proof = Proof::<S>::new(serialized_proof.clone());
 
     assert_eq!(proof.fill(), Ok(()));
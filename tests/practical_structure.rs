@@ -1,16 +1,18 @@
 use proof::impls::replace_index;
 use proof::node::Node;
-use proof::tree_arithmetic::zeroed::subtree_index_to_general;
+use proof::tree_arithmetic::zeroed::checked_subtree_index_to_general;
 use proof::types::{FixedVector, VariableList};
-use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
+use proof::{hash_children, Chunk, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
 use typenum::{U32, U8};
 
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct Message {
     timestamp: u64,
     message: FixedVector<u8, U32>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct State {
     messages: VariableList<Message, U8>,
@@ -21,6 +23,10 @@ impl MerkleTreeOverlay for Message {
         1
     }
 
+    fn max_depth() -> u64 {
+        Self::height() + FixedVector::<u8, U32>::max_depth()
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -43,7 +49,7 @@ impl MerkleTreeOverlay for Message {
             match FixedVector::<u8, U32>::get_node(path[1..].to_vec()) {
                 Ok(n) => Ok(replace_index(
                     n.clone(),
-                    subtree_index_to_general(2, n.index),
+                    checked_subtree_index_to_general(2, n.index).ok_or(Error::IndexOverflow())?,
                 )),
                 e => e,
             }
@@ -60,6 +66,10 @@ impl MerkleTreeOverlay for State {
         0
     }
 
+    fn max_depth() -> u64 {
+        VariableList::<Message, U8>::max_depth()
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -76,7 +86,7 @@ impl MerkleTreeOverlay for State {
                     index: 0,
                     offset: 0,
                     size: 0,
-                    height: VariableList::<Message, U8>::height().into(),
+                    height: VariableList::<Message, U8>::height(),
                     is_list: true,
                 })
             } else {
@@ -90,11 +100,11 @@ impl MerkleTreeOverlay for State {
     }
 }
 
-fn zero_hash(depth: u8) -> Vec<u8> {
+fn zero_hash(depth: u8) -> Chunk {
     if depth == 0 {
-        vec![0; 32]
+        Chunk::default()
     } else if depth == 1 {
-        hash_children(&[0; 32], &[0; 32])
+        hash_children(&Chunk::default(), &Chunk::default())
     } else {
         let last = zero_hash(depth - 1);
         hash_children(&last, &last)
@@ -109,19 +119,19 @@ fn roundtrip_partial() {
     arr[0] = 1;
 
     // 32 `message[0].message`
-    arr[32..64].copy_from_slice(&vec![1_u8; 32]);
+    arr[32..64].copy_from_slice(&[1_u8; 32]);
 
     // 33 `message[1].timestamp`
     arr[64] = 2;
 
     // 34 `message[1].message`
-    arr[96..128].copy_from_slice(&vec![42_u8; 32]);
+    arr[96..128].copy_from_slice(&[42_u8; 32]);
 
     // 8 `hash of message[2] and message[3]`
-    arr[128..160].copy_from_slice(&zero_hash(2));
+    arr[128..160].copy_from_slice(zero_hash(2).as_ref());
 
     // 4 `hash of message[4..7]`
-    arr[160..192].copy_from_slice(&zero_hash(3));
+    arr[160..192].copy_from_slice(zero_hash(3).as_ref());
 
     // 2 length mixin
     arr[223] = 2;
@@ -174,3 +184,74 @@ fn roundtrip_partial() {
         Ok(vec![42])
     );
 }
+
+#[test]
+fn get_and_set_typed_values() {
+    let mut arr = vec![0; 192];
+
+    // 31 `message[0].timestamp`
+    arr[0] = 1;
+
+    // 32 `message[0].message`
+    arr[32..64].copy_from_slice(&[1_u8; 32]);
+
+    // 16 `hash of message[1]`
+    arr[64..96].copy_from_slice(zero_hash(1).as_ref());
+
+    // 8 `hash of message[2] and message[3]`
+    arr[96..128].copy_from_slice(zero_hash(2).as_ref());
+
+    // 4 `hash of message[4..7]`
+    arr[128..160].copy_from_slice(zero_hash(3).as_ref());
+
+    // 2 length mixin
+    arr[160] = 1;
+
+    let sp = SerializedProof {
+        indices: vec![31, 32, 16, 8, 4, 2],
+        chunks: arr,
+    };
+
+    let mut proof = Proof::<State>::new(sp);
+    assert_eq!(proof.fill(), Ok(()));
+
+    let timestamp = || {
+        vec![
+            PathElement::from_ident_str("messages"),
+            PathElement::Index(0),
+            PathElement::from_ident_str("timestamp"),
+        ]
+    };
+
+    let message = |i: u64| {
+        vec![
+            PathElement::from_ident_str("messages"),
+            PathElement::Index(0),
+            PathElement::from_ident_str("message"),
+            PathElement::Index(i),
+        ]
+    };
+
+    assert_eq!(proof.get::<u64>(timestamp()), Ok(1));
+    assert_eq!(proof.get::<u8>(message(31)), Ok(1));
+
+    assert_eq!(proof.set::<u64>(timestamp(), &1_000_000), Ok(()));
+    assert_eq!(proof.set::<u8>(message(31), &42), Ok(()));
+
+    assert_eq!(proof.get::<u64>(timestamp()), Ok(1_000_000));
+    assert_eq!(proof.get::<u8>(message(31)), Ok(42));
+    assert_eq!(
+        proof.get_bytes(timestamp()),
+        Ok(vec![64, 66, 15, 0, 0, 0, 0, 0])
+    );
+
+    // Check for `Error::SizeMismatch(_, _)`
+    assert_eq!(
+        proof.get::<u32>(timestamp()),
+        Err(Error::SizeMismatch(8, 4))
+    );
+    assert_eq!(
+        proof.set::<u64>(message(0), &1),
+        Err(Error::SizeMismatch(1, 8))
+    );
+}
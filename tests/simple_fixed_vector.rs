@@ -1,7 +1,7 @@
 use ethereum_types::U256;
 use proof::node::Node;
 use proof::types::FixedVector;
-use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
+use proof::{hash_children, Chunk, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
 use typenum::U4;
 
 // S's merkle tree
@@ -11,6 +11,7 @@ use typenum::U4;
 //     i(1)       i(2)
 //     /  \       /  \
 //   a[0] a[1]  a[2] a[3]
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct S {
     a: FixedVector<U256, U4>,
@@ -22,6 +23,10 @@ impl MerkleTreeOverlay for S {
         0
     }
 
+    fn max_depth() -> u64 {
+        FixedVector::<U256, U4>::max_depth()
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -38,7 +43,7 @@ impl MerkleTreeOverlay for S {
                     index: 0,
                     offset: 0,
                     size: 32,
-                    height: FixedVector::<U256, U4>::height().into(),
+                    height: FixedVector::<U256, U4>::height(),
                     is_list: false,
                 })
             } else {
@@ -56,7 +61,7 @@ impl MerkleTreeOverlay for S {
 fn get_partial_vector() {
     let mut chunk = [0_u8; 96];
     chunk[31] = 1;
-    chunk[64..96].copy_from_slice(&hash_children(&[0; 32], &[0; 32]));
+    chunk[64..96].copy_from_slice(hash_children(&Chunk::default(), &Chunk::default()).as_ref());
 
     let proof = SerializedProof {
         indices: vec![5, 6, 1],
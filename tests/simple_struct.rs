@@ -1,6 +1,10 @@
 use ethereum_types::U256;
 use proof::node::Node;
-use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
+use proof::{
+    hash_children, Chunk, Error, GeneralizedIndex, MerkleTreeOverlay, NodeIndex, PathElement,
+    Proof, SerializedProof,
+};
+use std::convert::TryFrom;
 
 // A's merkle tree
 //
@@ -12,6 +16,7 @@ use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, Seriali
 //
 // n(i) => n = node type, i = general index
 // i = intermediate, p = padding
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct S {
     a: U256,
@@ -26,6 +31,10 @@ impl MerkleTreeOverlay for S {
         2
     }
 
+    fn max_depth() -> u64 {
+        2
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -107,8 +116,8 @@ fn roundtrip_partial() {
     four.to_little_endian(&mut arr[0..32]);
     three.to_little_endian(&mut arr[32..64]);
 
-    let two: &[u8] = &hash_children(&arr[64..96], &arr[64..96]);
-    arr[64..96].copy_from_slice(two);
+    let two = hash_children(&Chunk::default(), &Chunk::default());
+    arr[64..96].copy_from_slice(two.as_ref());
 
     let sp = SerializedProof {
         indices: vec![4, 3, 2],
@@ -119,3 +128,92 @@ fn roundtrip_partial() {
     assert_eq!(p.fill(), Ok(()));
     assert_eq!(Ok(sp), p.extract(vec![PathElement::from_ident_str("a")]));
 }
+
+#[test]
+fn roundtrip_generalized_indices() {
+    let mut arr = [0_u8; 96];
+
+    U256::from(2).to_little_endian(&mut arr[0..32]);
+    U256::from(1).to_little_endian(&mut arr[32..64]);
+
+    let two = hash_children(&Chunk::default(), &Chunk::default());
+    arr[64..96].copy_from_slice(two.as_ref());
+
+    // The same nodes as in `roundtrip_partial`, using spec indexing
+    let spec = SerializedProof {
+        indices: vec![5, 4, 3],
+        chunks: arr.to_vec(),
+    };
+
+    let sp = SerializedProof::from_generalized(spec.clone()).unwrap();
+    assert_eq!(sp.indices, vec![4, 3, 2]);
+
+    let mut p = Proof::<S>::new(sp);
+    assert_eq!(p.fill(), Ok(()));
+
+    let extracted = p.extract(vec![PathElement::from_ident_str("a")]).unwrap();
+    assert_eq!(extracted.to_generalized(), Ok(spec));
+
+    let a = S::get_node(vec![PathElement::from_ident_str("a")]).unwrap();
+    assert_eq!(
+        GeneralizedIndex::from_node_index(a.index).map(|g| g.get()),
+        Ok(4)
+    );
+
+    // Check for `Error::InvalidIndex(_)`
+    assert_eq!(
+        SerializedProof::from_generalized(SerializedProof {
+            indices: vec![0],
+            chunks: vec![0; 32],
+        })
+        .err(),
+        Some(Error::InvalidIndex(0))
+    );
+}
+
+#[test]
+fn load_invalid_proofs() {
+    let generate_proof = |indices: Vec<NodeIndex>, len: usize| SerializedProof {
+        indices,
+        chunks: vec![0; len],
+    };
+
+    assert!(Proof::<S>::try_from(generate_proof(vec![4, 3, 2], 96)).is_ok());
+
+    // Check for `Error::ChunkLengthMismatch(_, _)`
+    assert_eq!(
+        Proof::<S>::try_from(generate_proof(vec![4, 3, 2], 95)).err(),
+        Some(Error::ChunkLengthMismatch(96, 95))
+    );
+    assert_eq!(
+        Proof::<S>::try_from(generate_proof(vec![4, 3], 96)).err(),
+        Some(Error::ChunkLengthMismatch(64, 96))
+    );
+
+    // Check for `Error::DuplicateIndex(_)`
+    assert_eq!(
+        Proof::<S>::try_from(generate_proof(vec![4, 3, 4], 96)).err(),
+        Some(Error::DuplicateIndex(4))
+    );
+
+    // Check for `Error::InvalidIndex(_)`
+    assert_eq!(
+        Proof::<S>::try_from(generate_proof(vec![4, 7], 64)).err(),
+        Some(Error::InvalidIndex(7))
+    );
+    assert_eq!(
+        Proof::<S>::try_from(generate_proof(vec![NodeIndex::MAX], 32)).err(),
+        Some(Error::InvalidIndex(NodeIndex::MAX))
+    );
+
+    // A failed load leaves the proof unchanged
+    let mut p = Proof::<S>::default();
+    assert_eq!(
+        p.load(generate_proof(vec![3, 7], 64)),
+        Err(Error::InvalidIndex(7))
+    );
+    assert_eq!(
+        p.get_bytes(vec![PathElement::from_ident_str("a")]),
+        Err(Error::ChunkNotLoaded(3))
+    );
+}
@@ -1,6 +1,6 @@
 use proof::node::Node;
 use proof::types::VariableList;
-use proof::{hash_children, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
+use proof::{hash_children, Chunk, Error, MerkleTreeOverlay, PathElement, Proof, SerializedProof};
 use typenum::U4;
 
 // S's merkle tree
@@ -10,6 +10,7 @@ use typenum::U4;
 //    data_root(1) len(2)
 //      /     \
 // a[0,1](3) a[2,3](4)
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 struct S {
     a: VariableList<u128, U4>,
@@ -20,6 +21,10 @@ impl MerkleTreeOverlay for S {
         0
     }
 
+    fn max_depth() -> u64 {
+        VariableList::<u128, U4>::max_depth()
+    }
+
     fn min_repr_size() -> u64 {
         32
     }
@@ -36,7 +41,7 @@ impl MerkleTreeOverlay for S {
                     index: 0,
                     offset: 0,
                     size: 32,
-                    height: VariableList::<u128, U4>::height().into(),
+                    height: VariableList::<u128, U4>::height(),
                     is_list: true,
                 })
             } else {
@@ -57,7 +62,7 @@ fn roundtrip_partial() {
     chunk[31] = 2;
     chunk[47] = 3;
     chunk[63] = 4;
-    chunk[64..96].copy_from_slice(&hash_children(&[0; 32], &[0; 32]));
+    chunk[64..96].copy_from_slice(hash_children(&Chunk::default(), &Chunk::default()).as_ref());
 
     let proof = SerializedProof {
         indices: vec![3, 4, 2],
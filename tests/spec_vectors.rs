@@ -0,0 +1,955 @@
+//! Runs the `light_client/single_merkle_proof` test vectors of the consensus specification.
+//!
+//! Vectors are read from directories laid out like the consensus-spec-tests repository:
+//! `<preset>/<fork>/light_client/single_merkle_proof/<type>/<case>/`. Each case contains the SSZ
+//! encoded object in `object.ssz_snappy` and a `proof.yaml` with a `leaf`, its generalized
+//! `leaf_index` and the `branch` of sibling chunks ordered from the bottom of the tree up.
+//!
+//! For every vector the runner decodes the object and calculates its `hash_tree_root` with the
+//! SSZ schema below, which shares no code with the library. It then checks that the leaf is the
+//! root of the value at the case's path, that the branch verifies against the root of the
+//! object, that the overlay of the type resolves the path to `leaf_index` and that a proof
+//! loaded from the branch fills to the same root and extracts the same nodes.
+//!
+//! The vectors under `tests/fixtures/consensus-spec` are self-generated, not taken from a
+//! consensus-spec-tests release. They are random minimal preset objects of each fork in the same
+//! layout, with their leaves and branches calculated by an SSZ implementation outside the
+//! repository, and the SHA-256 hash of every file is recorded in
+//! `tests/fixtures/consensus-spec.sha256`. To run the official vectors, set
+//! `CONSENSUS_SPEC_TESTS` to the `tests` directory of a consensus-spec-tests checkout. Presets, forks, types and cases which are not registered below
+//! fail the run, so that no vector is silently left out.
+
+use ethereum_types::U256;
+use proof::tree_arithmetic::zeroed::get_branch_indices;
+use proof::{
+    impl_merkle_overlay_for_container, is_valid_merkle_branch, Chunk, GeneralizedIndex,
+    MerkleTreeOverlay, NodeIndex, Path, Proof, SerializedProof, BYTES_PER_CHUNK,
+};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fs;
+
+// `finality_root_merkle_proof` proves a field of a checkpoint, so its fields are modelled as
+// they are in the specification.
+struct Checkpoint;
+impl_merkle_overlay_for_container!(Checkpoint, [epoch: u64, root: U256]);
+
+// Fields which are not traversed by any vector are modelled as a single chunk. The fields added
+// to the state and the block body after altair do not change the depth of their trees, nor the
+// position of any field that is proven.
+struct BeaconState;
+impl_merkle_overlay_for_container!(
+    BeaconState,
+    [
+        genesis_time: U256,
+        genesis_validators_root: U256,
+        slot: U256,
+        fork: U256,
+        latest_block_header: U256,
+        block_roots: U256,
+        state_roots: U256,
+        historical_roots: U256,
+        eth1_data: U256,
+        eth1_data_votes: U256,
+        eth1_deposit_index: U256,
+        validators: U256,
+        balances: U256,
+        randao_mixes: U256,
+        slashings: U256,
+        previous_epoch_participation: U256,
+        current_epoch_participation: U256,
+        justification_bits: U256,
+        previous_justified_checkpoint: Checkpoint,
+        current_justified_checkpoint: Checkpoint,
+        finalized_checkpoint: Checkpoint,
+        inactivity_scores: U256,
+        current_sync_committee: U256,
+        next_sync_committee: U256,
+    ]
+);
+
+struct BeaconBlockBody;
+impl_merkle_overlay_for_container!(
+    BeaconBlockBody,
+    [
+        randao_reveal: U256,
+        eth1_data: U256,
+        graffiti: U256,
+        proposer_slashings: U256,
+        attester_slashings: U256,
+        attestations: U256,
+        deposits: U256,
+        voluntary_exits: U256,
+        sync_aggregate: U256,
+        execution_payload: U256,
+        bls_to_execution_changes: U256,
+    ]
+);
+
+/// An SSZ type, described as far as needed to decode a value and calculate its root.
+#[derive(Clone, Debug)]
+enum Type {
+    /// An unsigned integer, or a boolean, of the given number of bytes.
+    Uint(usize),
+    /// A fixed number of bytes.
+    Bytes(usize),
+    Bitvector(usize),
+    Bitlist(usize),
+    Vector(Box<Type>, usize),
+    List(Box<Type>, usize),
+    Container(Vec<(&'static str, Type)>),
+}
+
+use Type::*;
+
+fn vector(ty: Type, len: usize) -> Type {
+    Vector(Box::new(ty), len)
+}
+
+fn list(ty: Type, limit: usize) -> Type {
+    List(Box::new(ty), limit)
+}
+
+type Root = [u8; BYTES_PER_CHUNK];
+
+fn hash(left: &Root, right: &Root) -> Root {
+    let mut ret = Root::default();
+    ret.copy_from_slice(&Sha256::digest(&[&left[..], &right[..]].concat()));
+    ret
+}
+
+/// Merkleizes `chunks` into a tree with room for `limit` chunks.
+fn merkleize(mut chunks: Vec<Root>, limit: usize) -> Result<Root, String> {
+    if chunks.len() > limit {
+        return Err(format!(
+            "{} chunks exceed the limit of {}",
+            chunks.len(),
+            limit
+        ));
+    }
+
+    let depth = limit.next_power_of_two().trailing_zeros();
+    let mut zero = Root::default();
+
+    if chunks.is_empty() {
+        chunks.push(zero);
+    }
+
+    for _ in 0..depth {
+        if chunks.len() % 2 == 1 {
+            chunks.push(zero);
+        }
+
+        chunks = chunks
+            .chunks(2)
+            .map(|pair| hash(&pair[0], &pair[1]))
+            .collect();
+        zero = hash(&zero, &zero);
+    }
+
+    Ok(chunks[0])
+}
+
+fn mix_in_length(root: Root, len: usize) -> Root {
+    let mut length = Root::default();
+    length[..8].copy_from_slice(&(len as u64).to_le_bytes());
+    hash(&root, &length)
+}
+
+/// Packs `bytes` into zero padded chunks.
+fn pack(bytes: &[u8]) -> Vec<Root> {
+    bytes
+        .chunks(BYTES_PER_CHUNK)
+        .map(|chunk| {
+            let mut ret = Root::default();
+            ret[..chunk.len()].copy_from_slice(chunk);
+            ret
+        })
+        .collect()
+}
+
+fn read_offset(bytes: &[u8], at: usize) -> Result<usize, String> {
+    let offset = bytes.get(at..at + 4).ok_or("offset out of bounds")?;
+    Ok(u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize)
+}
+
+/// Splits `bytes` at `offsets`, checking that they are increasing and in bounds.
+fn split_at_offsets<'a>(bytes: &'a [u8], offsets: &[usize]) -> Result<Vec<&'a [u8]>, String> {
+    let ends = offsets.iter().skip(1).cloned().chain(Some(bytes.len()));
+
+    offsets
+        .iter()
+        .zip(ends)
+        .map(|(start, end)| {
+            bytes
+                .get(*start..end)
+                .ok_or_else(|| format!("invalid offsets {}..{}", start, end))
+        })
+        .collect()
+}
+
+impl Type {
+    fn fixed_size(&self) -> Option<usize> {
+        match self {
+            Uint(n) | Bytes(n) => Some(*n),
+            Bitvector(n) => Some(n.div_ceil(8)),
+            Vector(ty, n) => ty.fixed_size().map(|size| size * n),
+            List(..) | Bitlist(_) => None,
+            Container(fields) => fields.iter().map(|(_, ty)| ty.fixed_size()).sum(),
+        }
+    }
+
+    /// Splits the encoding of a sequence of `self` into its elements.
+    fn elements<'a>(&self, bytes: &'a [u8]) -> Result<Vec<&'a [u8]>, String> {
+        match self.fixed_size() {
+            Some(size) if bytes.len() % size == 0 => Ok(bytes.chunks(size).collect()),
+            Some(size) => Err(format!("{} bytes of {} byte elements", bytes.len(), size)),
+            None if bytes.is_empty() => Ok(vec![]),
+            None => {
+                let count = read_offset(bytes, 0)? / 4;
+                let offsets = (0..count)
+                    .map(|i| read_offset(bytes, 4 * i))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                split_at_offsets(bytes, &offsets)
+            }
+        }
+    }
+
+    /// Splits the encoding of a container into its fields.
+    fn fields<'a>(fields: &[(&str, Type)], bytes: &'a [u8]) -> Result<Vec<&'a [u8]>, String> {
+        let mut fixed = vec![];
+        let mut offsets = vec![];
+        let mut position = 0;
+
+        for (_, ty) in fields {
+            match ty.fixed_size() {
+                Some(size) => {
+                    fixed.push(Some(
+                        bytes.get(position..position + size).ok_or("too short")?,
+                    ));
+                    position += size;
+                }
+                None => {
+                    fixed.push(None);
+                    offsets.push(read_offset(bytes, position)?);
+                    position += 4;
+                }
+            }
+        }
+
+        if offsets
+            .first()
+            .map_or(position != bytes.len(), |first| *first != position)
+        {
+            return Err("fixed part does not end at the first offset".to_string());
+        }
+
+        let mut variable = split_at_offsets(bytes, &offsets)?.into_iter();
+        Ok(fixed
+            .into_iter()
+            .map(|field| field.or_else(|| variable.next()).unwrap())
+            .collect())
+    }
+
+    /// Calculates the `hash_tree_root` of the value of type `self` encoded in `bytes`.
+    fn hash_tree_root(&self, bytes: &[u8]) -> Result<Root, String> {
+        match self {
+            Uint(_) | Bytes(_) | Bitvector(_) if Some(bytes.len()) != self.fixed_size() => {
+                Err(format!("expected {:?} bytes", self.fixed_size()))
+            }
+            Uint(_) => Ok(pack(bytes)[0]),
+            Bytes(n) => merkleize(pack(bytes), n.div_ceil(32)),
+            Bitvector(n) => merkleize(pack(bytes), n.div_ceil(256)),
+            Bitlist(limit) => {
+                let last = *bytes
+                    .last()
+                    .filter(|b| **b != 0)
+                    .ok_or("missing delimiter")?;
+                let len = 8 * (bytes.len() - 1) + 7 - last.leading_zeros() as usize;
+
+                let mut bits = bytes.to_vec();
+                bits[bytes.len() - 1] ^= 1 << (len % 8);
+
+                let root = merkleize(pack(&bits[..len.div_ceil(8)]), limit.div_ceil(256))?;
+                Ok(mix_in_length(root, len))
+            }
+            Vector(ty, n) => {
+                let elements = ty.elements(bytes)?;
+
+                if elements.len() != *n {
+                    return Err(format!("expected {} elements", n));
+                }
+
+                ty.merkleize_elements(bytes, &elements, *n)
+            }
+            List(ty, limit) => {
+                let elements = ty.elements(bytes)?;
+                let root = ty.merkleize_elements(bytes, &elements, *limit)?;
+                Ok(mix_in_length(root, elements.len()))
+            }
+            Container(fields) => {
+                let roots = Type::fields(fields, bytes)?
+                    .into_iter()
+                    .zip(fields)
+                    .map(|(bytes, (_, ty))| ty.hash_tree_root(bytes))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                merkleize(roots, fields.len())
+            }
+        }
+    }
+
+    /// Merkleizes a sequence of up to `limit` elements of type `self`, packing basic values.
+    fn merkleize_elements(
+        &self,
+        bytes: &[u8],
+        elements: &[&[u8]],
+        limit: usize,
+    ) -> Result<Root, String> {
+        match self {
+            Uint(size) => merkleize(pack(bytes), (limit * size).div_ceil(32)),
+            _ => merkleize(
+                elements
+                    .iter()
+                    .map(|e| self.hash_tree_root(e))
+                    .collect::<Result<_, _>>()?,
+                limit,
+            ),
+        }
+    }
+
+    /// Calculates the `hash_tree_root` of the field at `path` of the container encoded in
+    /// `bytes`.
+    fn field_root(&self, bytes: &[u8], path: &[&str]) -> Result<Root, String> {
+        let (name, rest) = match (self, path.split_first()) {
+            (_, None) => return self.hash_tree_root(bytes),
+            (Container(_), Some(split)) => split,
+            (_, Some((name, _))) => return Err(format!("{} is not a container field", name)),
+        };
+
+        let fields = match self {
+            Container(fields) => fields,
+            _ => unreachable!(),
+        };
+
+        let position = fields
+            .iter()
+            .position(|(field, _)| field == name)
+            .ok_or_else(|| format!("unknown field {}", name))?;
+
+        fields[position]
+            .1
+            .field_root(Type::fields(fields, bytes)?[position], rest)
+    }
+}
+
+/// The constants of a preset which change the types of the objects.
+struct Preset {
+    slots_per_historical_root: usize,
+    epochs_per_historical_vector: usize,
+    epochs_per_slashings_vector: usize,
+    eth1_data_votes: usize,
+    sync_committee_size: usize,
+    max_withdrawals_per_payload: usize,
+    max_blob_commitments_per_block: usize,
+}
+
+fn preset(name: &str) -> Option<Preset> {
+    match name {
+        "mainnet" => Some(Preset {
+            slots_per_historical_root: 8192,
+            epochs_per_historical_vector: 65536,
+            epochs_per_slashings_vector: 8192,
+            eth1_data_votes: 64 * 32,
+            sync_committee_size: 512,
+            max_withdrawals_per_payload: 16,
+            max_blob_commitments_per_block: 4096,
+        }),
+        "minimal" => Some(Preset {
+            slots_per_historical_root: 64,
+            epochs_per_historical_vector: 64,
+            epochs_per_slashings_vector: 64,
+            eth1_data_votes: 4 * 8,
+            sync_committee_size: 32,
+            max_withdrawals_per_payload: 4,
+            max_blob_commitments_per_block: 32,
+        }),
+        _ => None,
+    }
+}
+
+const VALIDATOR_REGISTRY_LIMIT: usize = 1 << 40;
+const HISTORICAL_ROOTS_LIMIT: usize = 1 << 24;
+const MAX_VALIDATORS_PER_COMMITTEE: usize = 2048;
+
+fn checkpoint() -> Type {
+    Container(vec![("epoch", Uint(8)), ("root", Bytes(32))])
+}
+
+fn eth1_data() -> Type {
+    Container(vec![
+        ("deposit_root", Bytes(32)),
+        ("deposit_count", Uint(8)),
+        ("block_hash", Bytes(32)),
+    ])
+}
+
+fn beacon_block_header() -> Type {
+    Container(vec![
+        ("slot", Uint(8)),
+        ("proposer_index", Uint(8)),
+        ("parent_root", Bytes(32)),
+        ("state_root", Bytes(32)),
+        ("body_root", Bytes(32)),
+    ])
+}
+
+fn signed_beacon_block_header() -> Type {
+    Container(vec![
+        ("message", beacon_block_header()),
+        ("signature", Bytes(96)),
+    ])
+}
+
+fn attestation_data() -> Type {
+    Container(vec![
+        ("slot", Uint(8)),
+        ("index", Uint(8)),
+        ("beacon_block_root", Bytes(32)),
+        ("source", checkpoint()),
+        ("target", checkpoint()),
+    ])
+}
+
+fn indexed_attestation() -> Type {
+    Container(vec![
+        (
+            "attesting_indices",
+            list(Uint(8), MAX_VALIDATORS_PER_COMMITTEE),
+        ),
+        ("data", attestation_data()),
+        ("signature", Bytes(96)),
+    ])
+}
+
+/// Returns the execution payload of `fork`, or its header.
+fn execution_payload(fork: &str, preset: &Preset, header: bool) -> Type {
+    let mut fields = vec![
+        ("parent_hash", Bytes(32)),
+        ("fee_recipient", Bytes(20)),
+        ("state_root", Bytes(32)),
+        ("receipts_root", Bytes(32)),
+        ("logs_bloom", Bytes(256)),
+        ("prev_randao", Bytes(32)),
+        ("block_number", Uint(8)),
+        ("gas_limit", Uint(8)),
+        ("gas_used", Uint(8)),
+        ("timestamp", Uint(8)),
+        ("extra_data", list(Uint(1), 32)),
+        ("base_fee_per_gas", Uint(32)),
+        ("block_hash", Bytes(32)),
+    ];
+
+    if header {
+        fields.push(("transactions_root", Bytes(32)));
+    } else {
+        fields.push(("transactions", list(list(Uint(1), 1 << 30), 1 << 20)));
+    }
+
+    if fork != "bellatrix" {
+        if header {
+            fields.push(("withdrawals_root", Bytes(32)));
+        } else {
+            let withdrawal = Container(vec![
+                ("index", Uint(8)),
+                ("validator_index", Uint(8)),
+                ("address", Bytes(20)),
+                ("amount", Uint(8)),
+            ]);
+            fields.push((
+                "withdrawals",
+                list(withdrawal, preset.max_withdrawals_per_payload),
+            ));
+        }
+    }
+
+    if fork == "deneb" {
+        fields.push(("blob_gas_used", Uint(8)));
+        fields.push(("excess_blob_gas", Uint(8)));
+    }
+
+    Container(fields)
+}
+
+fn beacon_state(fork: &str, preset: &Preset) -> Type {
+    let validator = Container(vec![
+        ("pubkey", Bytes(48)),
+        ("withdrawal_credentials", Bytes(32)),
+        ("effective_balance", Uint(8)),
+        ("slashed", Uint(1)),
+        ("activation_eligibility_epoch", Uint(8)),
+        ("activation_epoch", Uint(8)),
+        ("exit_epoch", Uint(8)),
+        ("withdrawable_epoch", Uint(8)),
+    ]);
+    let sync_committee = Container(vec![
+        ("pubkeys", vector(Bytes(48), preset.sync_committee_size)),
+        ("aggregate_pubkey", Bytes(48)),
+    ]);
+    let fork_versions = Container(vec![
+        ("previous_version", Bytes(4)),
+        ("current_version", Bytes(4)),
+        ("epoch", Uint(8)),
+    ]);
+    let roots = vector(Bytes(32), preset.slots_per_historical_root);
+
+    let mut fields = vec![
+        ("genesis_time", Uint(8)),
+        ("genesis_validators_root", Bytes(32)),
+        ("slot", Uint(8)),
+        ("fork", fork_versions),
+        ("latest_block_header", beacon_block_header()),
+        ("block_roots", roots.clone()),
+        ("state_roots", roots),
+        ("historical_roots", list(Bytes(32), HISTORICAL_ROOTS_LIMIT)),
+        ("eth1_data", eth1_data()),
+        ("eth1_data_votes", list(eth1_data(), preset.eth1_data_votes)),
+        ("eth1_deposit_index", Uint(8)),
+        ("validators", list(validator, VALIDATOR_REGISTRY_LIMIT)),
+        ("balances", list(Uint(8), VALIDATOR_REGISTRY_LIMIT)),
+        (
+            "randao_mixes",
+            vector(Bytes(32), preset.epochs_per_historical_vector),
+        ),
+        (
+            "slashings",
+            vector(Uint(8), preset.epochs_per_slashings_vector),
+        ),
+        (
+            "previous_epoch_participation",
+            list(Uint(1), VALIDATOR_REGISTRY_LIMIT),
+        ),
+        (
+            "current_epoch_participation",
+            list(Uint(1), VALIDATOR_REGISTRY_LIMIT),
+        ),
+        ("justification_bits", Bitvector(4)),
+        ("previous_justified_checkpoint", checkpoint()),
+        ("current_justified_checkpoint", checkpoint()),
+        ("finalized_checkpoint", checkpoint()),
+        ("inactivity_scores", list(Uint(8), VALIDATOR_REGISTRY_LIMIT)),
+        ("current_sync_committee", sync_committee.clone()),
+        ("next_sync_committee", sync_committee),
+    ];
+
+    if fork != "altair" {
+        fields.push((
+            "latest_execution_payload_header",
+            execution_payload(fork, preset, true),
+        ));
+    }
+
+    if fork != "altair" && fork != "bellatrix" {
+        let summary = Container(vec![
+            ("block_summary_root", Bytes(32)),
+            ("state_summary_root", Bytes(32)),
+        ]);
+
+        fields.push(("next_withdrawal_index", Uint(8)));
+        fields.push(("next_withdrawal_validator_index", Uint(8)));
+        fields.push((
+            "historical_summaries",
+            list(summary, HISTORICAL_ROOTS_LIMIT),
+        ));
+    }
+
+    Container(fields)
+}
+
+fn beacon_block_body(fork: &str, preset: &Preset) -> Type {
+    let proposer_slashing = Container(vec![
+        ("signed_header_1", signed_beacon_block_header()),
+        ("signed_header_2", signed_beacon_block_header()),
+    ]);
+    let attester_slashing = Container(vec![
+        ("attestation_1", indexed_attestation()),
+        ("attestation_2", indexed_attestation()),
+    ]);
+    let attestation = Container(vec![
+        ("aggregation_bits", Bitlist(MAX_VALIDATORS_PER_COMMITTEE)),
+        ("data", attestation_data()),
+        ("signature", Bytes(96)),
+    ]);
+    let deposit = Container(vec![
+        ("proof", vector(Bytes(32), 33)),
+        (
+            "data",
+            Container(vec![
+                ("pubkey", Bytes(48)),
+                ("withdrawal_credentials", Bytes(32)),
+                ("amount", Uint(8)),
+                ("signature", Bytes(96)),
+            ]),
+        ),
+    ]);
+    let voluntary_exit = Container(vec![
+        (
+            "message",
+            Container(vec![("epoch", Uint(8)), ("validator_index", Uint(8))]),
+        ),
+        ("signature", Bytes(96)),
+    ]);
+    let sync_aggregate = Container(vec![
+        ("sync_committee_bits", Bitvector(preset.sync_committee_size)),
+        ("sync_committee_signature", Bytes(96)),
+    ]);
+    let bls_to_execution_change = Container(vec![
+        (
+            "message",
+            Container(vec![
+                ("validator_index", Uint(8)),
+                ("from_bls_pubkey", Bytes(48)),
+                ("to_execution_address", Bytes(20)),
+            ]),
+        ),
+        ("signature", Bytes(96)),
+    ]);
+
+    let mut fields = vec![
+        ("randao_reveal", Bytes(96)),
+        ("eth1_data", eth1_data()),
+        ("graffiti", Bytes(32)),
+        ("proposer_slashings", list(proposer_slashing, 16)),
+        ("attester_slashings", list(attester_slashing, 2)),
+        ("attestations", list(attestation, 128)),
+        ("deposits", list(deposit, 16)),
+        ("voluntary_exits", list(voluntary_exit, 16)),
+        ("sync_aggregate", sync_aggregate),
+    ];
+
+    if fork != "altair" {
+        fields.push(("execution_payload", execution_payload(fork, preset, false)));
+    }
+
+    if fork != "altair" && fork != "bellatrix" {
+        fields.push((
+            "bls_to_execution_changes",
+            list(bls_to_execution_change, 16),
+        ));
+    }
+
+    if fork == "deneb" {
+        fields.push((
+            "blob_kzg_commitments",
+            list(Bytes(48), preset.max_blob_commitments_per_block),
+        ));
+    }
+
+    Container(fields)
+}
+
+/// The contents of a `proof.yaml` file.
+#[derive(Debug, Default)]
+struct Vector {
+    leaf: Chunk,
+    leaf_index: NodeIndex,
+    branch: Vec<Chunk>,
+}
+
+/// Parses a `proof.yaml` file. Only the subset of YAML used by the vectors is supported, with
+/// the branch given either as a block or a flow sequence.
+fn parse_vector(yaml: &str) -> Vector {
+    let chunk = |s: &str| {
+        let hex = s.trim().trim_matches(|c| c == '\'' || c == '"');
+        let hex = hex.strip_prefix("0x").expect("chunk to be 0x prefixed");
+        let mut ret = Chunk::default();
+
+        for (i, byte) in ret.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).expect("chunk to be hex");
+        }
+
+        ret
+    };
+
+    let mut ret = Vector::default();
+
+    for line in yaml.lines().filter(|line| !line.trim().is_empty()) {
+        if let Some(item) = line.trim().strip_prefix("- ") {
+            ret.branch.push(chunk(item));
+            continue;
+        }
+
+        let (key, value) = line.split_at(line.find(':').expect("line to be a key"));
+        let value = value[1..].trim();
+
+        match key.trim() {
+            "leaf" => ret.leaf = chunk(value),
+            "leaf_index" => ret.leaf_index = value.parse().expect("leaf index to be a number"),
+            "branch" if !value.is_empty() => {
+                let items = value.trim_start_matches('[').trim_end_matches(']');
+                ret.branch = items.split(',').map(chunk).collect();
+            }
+            _ => (),
+        }
+    }
+
+    ret
+}
+
+/// Returns the path proven by each case.
+fn case_path(case: &str) -> Option<&'static str> {
+    match case {
+        "current_sync_committee_merkle_proof" => Some("current_sync_committee"),
+        "next_sync_committee_merkle_proof" => Some("next_sync_committee"),
+        "finality_root_merkle_proof" => Some("finalized_checkpoint.root"),
+        "execution_merkle_proof" => Some("execution_payload"),
+        _ => None,
+    }
+}
+
+/// A runner for the vectors of a type, along with the SSZ type of their objects.
+type Runner = (fn(&Vector, &str, &Type, &[u8]), Type);
+
+/// Returns the runner for the vectors of type `name` in `fork`.
+fn registered(preset: &Preset, fork: &str, name: &str) -> Option<Runner> {
+    match (fork, name) {
+        ("altair", "BeaconState")
+        | ("bellatrix", "BeaconState")
+        | ("capella", "BeaconState")
+        | ("deneb", "BeaconState") => Some((run_vector::<BeaconState>, beacon_state(fork, preset))),
+        ("capella", "BeaconBlockBody") | ("deneb", "BeaconBlockBody") => Some((
+            run_vector::<BeaconBlockBody>,
+            beacon_block_body(fork, preset),
+        )),
+        _ => None,
+    }
+}
+
+/// Checks a single vector of an object of SSZ type `ty` against the overlay of `T`.
+fn run_vector<T: MerkleTreeOverlay>(vector: &Vector, path: &str, ty: &Type, object: &[u8]) {
+    let root = ty.hash_tree_root(object).unwrap();
+    let fields: Vec<&str> = path.split('.').collect();
+    assert_eq!(ty.field_root(object, &fields).unwrap(), *vector.leaf);
+
+    let depth = vector.branch.len() as u64;
+    let position = vector.leaf_index - (1 << depth);
+    let root = Chunk(root);
+
+    assert_eq!(
+        is_valid_merkle_branch(&vector.leaf, &vector.branch, depth, position, &root),
+        Ok(())
+    );
+
+    // A branch for a different position is rejected
+    assert!(
+        is_valid_merkle_branch(&vector.leaf, &vector.branch, depth, position ^ 1, &root).is_err()
+    );
+
+    let path: Path = path.parse().unwrap();
+    let node = T::get_node(path.clone().into()).unwrap();
+
+    assert_eq!(
+        GeneralizedIndex::from_node_index(node.index).map(GeneralizedIndex::get),
+        Ok(vector.leaf_index)
+    );
+
+    let indices = get_branch_indices(node.index);
+    assert_eq!(indices.len(), vector.branch.len());
+
+    let nodes: BTreeMap<NodeIndex, Chunk> = indices
+        .into_iter()
+        .zip(vector.branch.iter().cloned())
+        .chain(std::iter::once((node.index, vector.leaf)))
+        .collect();
+
+    let mut p = Proof::<T>::try_from(SerializedProof::from_nodes(nodes.clone())).unwrap();
+    assert_eq!(p.fill(), Ok(()));
+    assert_eq!(p.verify(&root), Ok(()));
+
+    let extracted = p.extract(path).unwrap();
+    assert_eq!(extracted.nodes().collect::<BTreeMap<_, _>>(), nodes);
+}
+
+/// Lists the entries of `dir`, sorted by name.
+fn entries(dir: &std::path::Path) -> Vec<(String, std::path::PathBuf)> {
+    let mut ret: Vec<_> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).collect())
+        .unwrap_or_else(|_| vec![]);
+
+    ret.sort_by_key(|e| e.file_name());
+    ret.into_iter()
+        .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path()))
+        .collect()
+}
+
+/// Runs every vector below `root` and returns the number of vectors which were run.
+///
+/// # Panics
+/// Panics if any vector fails, or if any preset, fork, type or case is not registered.
+fn run_vectors(root: &std::path::Path) -> usize {
+    let mut ret = 0;
+    let mut unsupported = vec![];
+
+    for (preset_name, dir) in entries(root) {
+        for (fork, dir) in entries(&dir) {
+            let dir = dir.join("light_client").join("single_merkle_proof");
+
+            for (name, dir) in entries(&dir) {
+                let runner =
+                    preset(&preset_name).and_then(|preset| registered(&preset, &fork, &name));
+
+                for (case, dir) in entries(&dir) {
+                    let (run, ty, path) = match (&runner, case_path(&case)) {
+                        (Some((run, ty)), Some(path)) => (run, ty, path),
+                        _ => {
+                            unsupported.push(format!("{}/{}/{}/{}", preset_name, fork, name, case));
+                            continue;
+                        }
+                    };
+
+                    let yaml = fs::read_to_string(dir.join("proof.yaml")).unwrap();
+                    let object = snap::raw::Decoder::new()
+                        .decompress_vec(&fs::read(dir.join("object.ssz_snappy")).unwrap())
+                        .unwrap();
+
+                    run(&parse_vector(&yaml), path, ty, &object);
+                    ret += 1;
+                }
+            }
+        }
+    }
+
+    assert!(
+        unsupported.is_empty(),
+        "unsupported vectors: {:#?}",
+        unsupported
+    );
+
+    ret
+}
+
+#[test]
+fn checked_in_vectors() {
+    let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let sums = fs::read_to_string(fixtures.join("consensus-spec.sha256")).unwrap();
+
+    for line in sums.lines() {
+        let (sum, file) = line.split_at(line.find("  ").expect("line to be a checksum"));
+        let bytes = fs::read(fixtures.join(file.trim())).unwrap();
+        let hash: String = Sha256::digest(&bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(hash, sum, "{}", file.trim());
+    }
+
+    assert_eq!(sums.lines().count(), 14);
+    assert_eq!(run_vectors(&fixtures.join("consensus-spec")), 7);
+}
+
+#[test]
+fn finalized_checkpoint() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(
+        "tests/fixtures/consensus-spec/minimal/altair/light_client/single_merkle_proof/\
+         BeaconState/finality_root_merkle_proof",
+    );
+    let vector = parse_vector(&fs::read_to_string(dir.join("proof.yaml")).unwrap());
+    let object = snap::raw::Decoder::new()
+        .decompress_vec(&fs::read(dir.join("object.ssz_snappy")).unwrap())
+        .unwrap();
+
+    let node =
+        BeaconState::get_node("finalized_checkpoint.root".parse::<Path>().unwrap().into()).unwrap();
+    let nodes: BTreeMap<NodeIndex, Chunk> = get_branch_indices(node.index)
+        .into_iter()
+        .zip(vector.branch.iter().cloned())
+        .chain(std::iter::once((node.index, vector.leaf)))
+        .collect();
+
+    let mut p = Proof::<BeaconState>::try_from(SerializedProof::from_nodes(nodes)).unwrap();
+    assert_eq!(p.fill(), Ok(()));
+
+    // The epoch is the sibling of the root, so both fields can be read through the overlay
+    let ty = beacon_state("altair", &preset("minimal").unwrap());
+    let epoch = ty
+        .field_root(&object, &["finalized_checkpoint", "epoch"])
+        .unwrap();
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&epoch[..8]);
+
+    assert_eq!(
+        p.get::<u64>("finalized_checkpoint.epoch".parse::<Path>().unwrap()),
+        Ok(u64::from_le_bytes(bytes))
+    );
+    assert_eq!(
+        p.get::<U256>("finalized_checkpoint.root".parse::<Path>().unwrap()),
+        Ok(U256::from_little_endian(&*vector.leaf))
+    );
+}
+
+#[test]
+fn consensus_spec_vectors() {
+    if let Ok(root) = std::env::var("CONSENSUS_SPEC_TESTS") {
+        assert!(run_vectors(std::path::Path::new(&root)) > 0);
+    }
+}
+
+#[test]
+fn parse_flow_sequences() {
+    let zero = format!("0x{}", "00".repeat(32));
+    let one = format!("0x{}", "11".repeat(32));
+    let yaml = format!(
+        "leaf: {}\nleaf_index: 6\nbranch: ['{}', '{}']\n",
+        one, zero, one
+    );
+
+    let vector = parse_vector(&yaml);
+    assert_eq!(vector.leaf, Chunk([0x11; 32]));
+    assert_eq!(vector.leaf_index, 6);
+    assert_eq!(vector.branch, vec![Chunk::default(), Chunk([0x11; 32])]);
+}
+
+#[test]
+fn ssz_roots() {
+    // hash_tree_root(uint64(5))
+    let mut five = Root::default();
+    five[0] = 5;
+    assert_eq!(Uint(8).hash_tree_root(&[5, 0, 0, 0, 0, 0, 0, 0]), Ok(five));
+
+    // An empty list is the zero tree of its limit mixed with a length of zero
+    let zero = Root::default();
+    let zeros = hash(&zero, &zero);
+    let empty = hash(&hash(&zeros, &zeros), &zero);
+    assert_eq!(list(Bytes(32), 4).hash_tree_root(&[]), Ok(empty));
+
+    // Bitlists drop their delimiting bit before packing
+    let mut bits = Root::default();
+    bits[0] = 0b101;
+    let mut length = Root::default();
+    length[0] = 3;
+    assert_eq!(
+        Bitlist(8).hash_tree_root(&[0b1101]),
+        Ok(hash(&bits, &length))
+    );
+
+    // Variable size fields are found through their offsets
+    let ty = Container(vec![
+        ("a", Uint(2)),
+        ("b", list(Uint(1), 32)),
+        ("c", Uint(1)),
+    ]);
+    let bytes = [1, 0, 7, 0, 0, 0, 2, 9, 9];
+    let b = mix_in_length(pack(&[9, 9])[0], 2);
+    let mut a = Root::default();
+    a[0] = 1;
+    let mut c = Root::default();
+    c[0] = 2;
+    assert_eq!(
+        ty.hash_tree_root(&bytes),
+        Ok(hash(&hash(&a, &b), &hash(&c, &zero)))
+    );
+    assert_eq!(ty.field_root(&bytes, &["b"]), Ok(b));
+    assert!(ty.hash_tree_root(&bytes[..6]).is_err());
+}